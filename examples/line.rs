@@ -9,6 +9,7 @@ use winit::{
 use anyhow::Result;
 use app_engine::{
     render::*,
+    tessellate::stroke::*,
     math::*,
     Color,
     AppEngine,
@@ -31,13 +32,13 @@ impl TriangleExample {
         log::debug!("Frame time: {duration:?}");
         let dt = duration.as_secs_f32();
         if self.move_up {
-            self.transform.translation.y += 0.5 * dt;
-            if self.transform.translation.y >= 0.25 {
+            self.transform.translation.y += 200.0 * dt;
+            if self.transform.translation.y >= 250.0 {
                 self.move_up = false;
             }
         } else {
-            self.transform.translation.y -= 0.5 * dt;
-            if self.transform.translation.y <= -1.0 {
+            self.transform.translation.y -= 200.0 * dt;
+            if self.transform.translation.y <= 0.0 {
                 self.move_up = true;
             }
         }
@@ -50,22 +51,25 @@ impl TriangleExample {
 impl App for TriangleExample {
     fn new(el: &ActiveEventLoop, _: EventLoopProxy<()>)->Result<Self> {
         let mut render = Renderer::new(el, "Triangle Example")?;
-        let shape = render.add_shape2d(Shape2D::Line(
-            vec![
+        let shape = render.add_shape2d(Shape2D::Line {
+            colors: vec![
                 Color(1.0, 0.0, 0.0, 1.0),
                 Color(0.0, 1.0, 0.0, 1.0),
                 Color(0.0, 0.0, 1.0, 1.0),
             ],
-            vec![
-                Point2::new(0.0, 0.75),
-                Point2::new(0.375, 0.0),
-                Point2::new(-0.5, 0.0),
+            points: vec![
+                Point2::new(0.0, 300.0),
+                Point2::new(150.0, 0.0),
+                Point2::new(-200.0, 0.0),
             ],
-        ))?;
+            style: LineStyle::new(8.0)
+                .with_join(LineJoin::Round)
+                .with_cap(LineCap::Round),
+        })?;
         return Ok(TriangleExample {
             render,
             shape,
-            transform: Transform2::new(Point2::new(0.0, 0.0), Rotation2::from_angle(0.0), 1.0),
+            transform: Transform2::new(Point2::new(400.0, 100.0), Rotation2::from_angle(0.0), 1.0),
             move_up: false,
             last_frame: Instant::now(),
         });
@@ -31,13 +31,13 @@ impl TriangleExample {
         log::debug!("Frame time: {duration:?}");
         let dt = duration.as_secs_f32();
         if self.move_up {
-            self.transform.translation.y += 0.5 * dt;
-            if self.transform.translation.y >= 0.0 {
+            self.transform.translation.y += 200.0 * dt;
+            if self.transform.translation.y >= 200.0 {
                 self.move_up = false;
             }
         } else {
-            self.transform.translation.y -= 0.5 * dt;
-            if self.transform.translation.y <= -1.0 {
+            self.transform.translation.y -= 200.0 * dt;
+            if self.transform.translation.y <= 0.0 {
                 self.move_up = true;
             }
         }
@@ -57,9 +57,9 @@ impl App for TriangleExample {
             indices: vec![0, 1, 2, 3, 2, 1],
             vertices: vec![
                 Point2::new(0.0, 0.0),
-                Point2::new(0.0, 1.0),
-                Point2::new(1.0, 0.0),
-                Point2::new(1.0, 1.0),
+                Point2::new(0.0, 400.0),
+                Point2::new(400.0, 0.0),
+                Point2::new(400.0, 400.0),
             ],
             uvs: vec![
                 Point2::new(0.0, 0.0),
@@ -71,7 +71,7 @@ impl App for TriangleExample {
         return Ok(TriangleExample {
             render,
             shape,
-            transform: Transform2::new(Point2::new(0.0, 0.0), Rotation2::from_angle(0.0), 1.0),
+            transform: Transform2::new(Point2::new(400.0, 100.0), Rotation2::from_angle(0.0), 1.0),
             move_up: false,
             last_frame: Instant::now(),
         });
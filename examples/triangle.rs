@@ -31,13 +31,13 @@ impl TriangleExample {
         log::debug!("Frame time: {duration:?}");
         let dt = duration.as_secs_f32();
         if self.move_up {
-            self.transform.translation.y += 0.5 * dt;
-            if self.transform.translation.y >= 0.25 {
+            self.transform.translation.y += 200.0 * dt;
+            if self.transform.translation.y >= 250.0 {
                 self.move_up = false;
             }
         } else {
-            self.transform.translation.y -= 0.5 * dt;
-            if self.transform.translation.y <= -1.0 {
+            self.transform.translation.y -= 200.0 * dt;
+            if self.transform.translation.y <= 0.0 {
                 self.move_up = true;
             }
         }
@@ -53,9 +53,9 @@ impl App for TriangleExample {
         let shape = render.add_shape2d(Shape2D::ColorPolygon {
             indices: vec![0, 1, 2],
             vertices: vec![
-                Point2::new(0.0, 0.75),
-                Point2::new(0.375, 0.0),
-                Point2::new(-0.375, 0.0),
+                Point2::new(0.0, 300.0),
+                Point2::new(150.0, 0.0),
+                Point2::new(-150.0, 0.0),
             ],
             colors: vec![
                 Color(1.0, 0.0, 0.0, 1.0),
@@ -66,7 +66,7 @@ impl App for TriangleExample {
         return Ok(TriangleExample {
             render,
             shape,
-            transform: Transform2::new(Point2::new(0.0, 0.0), Rotation2::from_angle(0.0), 1.0),
+            transform: Transform2::new(Point2::new(400.0, 100.0), Rotation2::from_angle(0.0), 1.0),
             move_up: false,
             last_frame: Instant::now(),
         });
@@ -2,8 +2,8 @@
 
 layout(location = 0) in vec4 color;
 
-layout(location = 0) out vec4 vk_Color;
+layout(location = 0) out vec4 vk_color;
 
 void main() {
-    vk_Color = color;
+    vk_color = color;
 }
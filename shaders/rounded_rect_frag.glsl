@@ -0,0 +1,54 @@
+#version 460 core
+
+layout(location = 0) in vec2 local;
+layout(location = 1) flat in vec2 half_size;
+layout(location = 2) flat in vec4 radii;
+layout(location = 3) flat in vec4 fill;
+layout(location = 4) flat in vec4 border_color;
+layout(location = 5) flat in float border_width;
+layout(location = 6) flat in vec4 shadow_color;
+layout(location = 7) flat in vec2 shadow_offset;
+layout(location = 8) flat in float shadow_blur;
+layout(location = 9) flat in float shadow_spread;
+layout(location = 10) flat in vec4 tint;
+
+layout(location = 0) out vec4 vk_color;
+
+// Signed distance to the edge of a rounded rectangle centered on the origin. The corners are top
+// left, top right, bottom right and bottom left, with +Y going down.
+float rounded_box(vec2 p, vec2 half_size, vec4 radii) {
+    vec2 side = p.x > 0.0 ? radii.yz : radii.xw;
+    float radius = p.y > 0.0 ? side.y : side.x;
+    vec2 q = abs(p) - half_size + radius;
+    return min(max(q.x, q.y), 0.0) + length(max(q, 0.0)) - radius;
+}
+
+void main() {
+    float d = rounded_box(local, half_size, radii);
+    // One pixel, however big the camera makes the rectangle
+    float aa = max(fwidth(d), 0.0001);
+    float coverage = clamp(0.5 - d / aa, 0.0, 1.0);
+
+    vec4 body = fill;
+    if (border_width > 0.0) {
+        float inside_border = clamp(0.5 - (d + border_width) / aa, 0.0, 1.0);
+        body = mix(border_color, fill, inside_border);
+    }
+    body.a *= coverage;
+
+    // The shadow is only drawn outside of the rectangle, like a CSS box shadow
+    float shadow_d = rounded_box(
+        local - shadow_offset,
+        max(half_size + shadow_spread, vec2(0.0)),
+        max(radii + shadow_spread, vec4(0.0))
+    );
+    float fade = shadow_blur * 0.5 + aa * 0.5;
+    float shadow_alpha = shadow_color.a * (1.0 - smoothstep(-fade, fade, shadow_d)) * (1.0 - coverage);
+
+    float alpha = body.a + shadow_alpha * (1.0 - body.a);
+    vec3 rgb = vec3(0.0);
+    if (alpha > 0.0) {
+        rgb = (body.rgb * body.a + shadow_color.rgb * shadow_alpha * (1.0 - body.a)) / alpha;
+    }
+    vk_color = vec4(rgb, alpha) * tint;
+}
@@ -0,0 +1,60 @@
+#version 460 core
+
+// One transform per instance. A single draw is one instance.
+layout(binding = 1, std430) readonly buffer Transforms {
+    mat3 transforms[];
+};
+
+// Multiplied with the color of each instance
+layout(binding = 2, std430) readonly buffer Tints {
+    vec4 tints[];
+};
+
+// The depth of each instance's layer
+layout(binding = 3, std430) readonly buffer Depths {
+    float depths[];
+};
+
+layout(location = 0) in vec2 position;
+// Position relative to the center of the rectangle
+layout(location = 1) in vec2 local_in;
+layout(location = 2) in vec2 half_size_in;
+// Top left, top right, bottom right and bottom left
+layout(location = 3) in vec4 radii_in;
+layout(location = 4) in vec4 fill_in;
+layout(location = 5) in vec4 border_color_in;
+layout(location = 6) in float border_width_in;
+layout(location = 7) in vec4 shadow_color_in;
+layout(location = 8) in vec2 shadow_offset_in;
+layout(location = 9) in float shadow_blur_in;
+layout(location = 10) in float shadow_spread_in;
+
+layout(location = 0) out vec2 local;
+layout(location = 1) flat out vec2 half_size;
+layout(location = 2) flat out vec4 radii;
+layout(location = 3) flat out vec4 fill;
+layout(location = 4) flat out vec4 border_color;
+layout(location = 5) flat out float border_width;
+layout(location = 6) flat out vec4 shadow_color;
+layout(location = 7) flat out vec2 shadow_offset;
+layout(location = 8) flat out float shadow_blur;
+layout(location = 9) flat out float shadow_spread;
+layout(location = 10) flat out vec4 tint;
+
+void main() {
+    mat3 transform = transforms[gl_InstanceIndex];
+    vec3 clip_position = transform * vec3(position, 1.0);
+    gl_Position = vec4(clip_position.xy, depths[gl_InstanceIndex], 1);
+
+    local = local_in;
+    half_size = half_size_in;
+    radii = radii_in;
+    fill = fill_in;
+    border_color = border_color_in;
+    border_width = border_width_in;
+    shadow_color = shadow_color_in;
+    shadow_offset = shadow_offset_in;
+    shadow_blur = shadow_blur_in;
+    shadow_spread = shadow_spread_in;
+    tint = tints[gl_InstanceIndex];
+}
@@ -1,15 +1,30 @@
 #version 460 core
 
-layout(push_constant) uniform pc {
-    mat3 transform;
+// One transform per instance. A single draw is one instance.
+layout(binding = 1, std430) readonly buffer Transforms {
+    mat3 transforms[];
+};
+
+// Multiplied with the texture color of each instance
+layout(binding = 2, std430) readonly buffer Tints {
+    vec4 tints[];
+};
+
+// The depth of each instance's layer
+layout(binding = 3, std430) readonly buffer Depths {
+    float depths[];
 };
 
 layout(location = 0) in vec2 position;
 layout(location = 1) in vec2 uv_in;
 
 layout(location = 0) out vec2 uv;
+layout(location = 1) out vec4 tint;
 
 void main() {
-    gl_Position = vec4(transform * vec3(position, 1.0), 1);
+    mat3 transform = transforms[gl_InstanceIndex];
+    vec3 clip_position = transform * vec3(position, 1.0);
+    gl_Position = vec4(clip_position.xy, depths[gl_InstanceIndex], 1);
     uv = uv_in;
+    tint = tints[gl_InstanceIndex];
 }
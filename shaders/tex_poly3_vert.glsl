@@ -0,0 +1,16 @@
+#version 460 core
+
+// The camera's view projection combined with the shape transform
+layout(push_constant, std430) uniform PushConstants {
+    mat4 transform;
+};
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec2 uv_in;
+
+layout(location = 0) out vec2 uv;
+
+void main() {
+    gl_Position = transform * vec4(position, 1.0);
+    uv = uv_in;
+}
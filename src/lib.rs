@@ -5,6 +5,7 @@
 //! - UI module
 //! - Simple render module for 2D applications with the option to write a custom 3d rendering
 //!     pipeline
+//! - Tessellation of thick lines and vector paths into triangles
 //! - Allows access to internals of the renderer, ui state, etc. for custom behavior. This might
 //!     be seen as an abuse of `pub`, but this crate is not being designed for an end-user, but
 //!     instead to be dog-fooded into my other projects, like games. Games usually require a bit
@@ -37,6 +38,7 @@ use std::marker::PhantomData;
 
 
 pub mod render;
+pub mod tessellate;
 pub mod ui;
 
 pub mod math {
@@ -80,13 +82,45 @@ pub trait App<T: 'static = ()>: Sized {
 
 
 #[repr(C,align(4))]
-#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable, serde::Serialize, serde::Deserialize)]
 pub struct Color {
     pub r: f32,
     pub g: f32,
     pub b: f32,
     pub a: f32,
 }
+impl Color {
+    pub const WHITE: Self = Color {r: 1.0, g: 1.0, b: 1.0, a: 1.0};
+
+    /// Blend linearly between `self` at `t = 0` and `other` at `t = 1`
+    pub fn lerp(&self, other: &Self, t: f32)->Self {
+        Color {
+            r: self.r + (other.r - self.r) * t,
+            g: self.g + (other.g - self.g) * t,
+            b: self.b + (other.b - self.b) * t,
+            a: self.a + (other.a - self.a) * t,
+        }
+    }
+
+    /// Convert to 8 bit sRGB with linear alpha, the format used by [`render::Renderer`] images.
+    pub fn to_srgba8(&self)->[u8;4] {
+        fn encode(c: f32)->f32 {
+            let c = c.clamp(0.0, 1.0);
+            if c <= 0.0031308 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        }
+
+        return [
+            (encode(self.r) * 255.0).round() as u8,
+            (encode(self.g) * 255.0).round() as u8,
+            (encode(self.b) * 255.0).round() as u8,
+            (self.a.clamp(0.0, 1.0) * 255.0).round() as u8,
+        ];
+    }
+}
 
 #[derive(Debug)]
 pub struct AppEngine<EV: 'static, T: App<EV>> {
@@ -17,6 +17,10 @@ use log::{
 use image::RgbaImage;
 use std::sync::Arc;
 use crate::{
+    tessellate::stroke::{
+        LineStyle,
+        stroke,
+    },
     math::*,
     Uuid,
     IdMap,
@@ -25,8 +29,13 @@ use crate::{
 
 
 pub enum Shape2D {
-    /// A list of points where each point connects to the next one to form a line
-    Line(Vec<Color>, Vec<Point2>),
+    /// A list of points where each point connects to the next one to form a line. It is
+    /// tessellated into a [`Shape2D::ColorPolygon`] when it is added.
+    Line {
+        colors: Vec<Color>,
+        points: Vec<Point2>,
+        style: LineStyle,
+    },
     ColorPolygon {
         colors: Vec<Color>,
         /// The vertex positions for the triangles
@@ -45,7 +54,7 @@ pub enum Shape2D {
     },
 }
 
-/// TODO(3d shapes): Implement all the things to render 3d shapes
+/// A 3D shape. Drawn with depth testing through [`RenderFrame::shape3d`].
 pub enum Shape3 {
     ColorPolygon {
         colors: Vec<Color>,
@@ -55,7 +64,7 @@ pub enum Shape3 {
         indices: Vec<u16>,
     },
     TexturePolygon {
-        texture: Uuid,
+        texture: ImageID,
         /// The UV for each vertex
         uvs: Vec<Point2>,
         /// The vertex positions for the triangles
@@ -65,8 +74,23 @@ pub enum Shape3 {
     },
 }
 
+#[derive(Clone)]
+pub enum Shape3Internal {
+    ColorPoly {
+        vertex_color: Arc<Buffer>,
+        index_count: u32,
+        index: Arc<Buffer>,
+    },
+    TexturePoly {
+        vert_uv: Arc<Buffer>,
+        index_count: u32,
+        index: Arc<Buffer>,
+        texture: Arc<Image>,
+    },
+}
+
+#[derive(Clone)]
 pub enum Shape2DInternal {
-    Line(Arc<Buffer>, u32),
     ColorPoly {
         vertex_color: Arc<Buffer>,
         index_count: u32,
@@ -79,28 +103,307 @@ pub enum Shape2DInternal {
         texture: Arc<Image>,
     },
 }
+impl Shape2DInternal {
+    pub fn pipeline(&self)->Pipeline2D {
+        match self {
+            Self::ColorPoly{..}=>Pipeline2D::ColorPoly,
+            Self::TexturePoly{..}=>Pipeline2D::TexturePoly,
+        }
+    }
+
+    /// Can both shapes be drawn in the same pass
+    fn same_batch(&self, other: &Self)->bool {
+        match (self, other) {
+            (Self::ColorPoly{..}, Self::ColorPoly{..})=>true,
+            (Self::TexturePoly{texture: a, ..}, Self::TexturePoly{texture: b, ..})=>Arc::ptr_eq(a, b),
+            _=>false,
+        }
+    }
+}
+
+/// A buffer picked by [`Renderer::prepare_buffer`]
+enum BufferUpload<'a> {
+    /// An old buffer that nothing else holds. The data is written into it when finished.
+    InPlace(&'a mut Arc<Buffer>),
+    /// A new buffer that already holds the data
+    New(Arc<Buffer>),
+}
+impl BufferUpload<'_> {
+    /// Write `data` into the buffer if it is an old one. This can't fail.
+    fn finish(self, data: &[u8])->Arc<Buffer> {
+        match self {
+            Self::InPlace(buffer)=>{
+                let Some(inner) = Arc::get_mut(buffer) else {unreachable!("The buffer was checked to be unique")};
+                Buffer::copy_from_slice(inner, 0, data);
+                buffer.clone()
+            },
+            Self::New(buffer)=>buffer,
+        }
+    }
+}
+
+/// Where the geometry of a queued draw is
+#[derive(Clone)]
+enum DrawSource {
+    Shape(Shape2DInternal),
+    /// Immediate geometry in the frame's stream buffers. Indices are relative to `first_vertex`.
+    Stream {
+        pipeline: Pipeline2D,
+        first_vertex: u32,
+        vertex_count: u32,
+        first_index: u32,
+        index_count: u32,
+    },
+}
+impl DrawSource {
+    fn pipeline(&self)->Pipeline2D {
+        match self {
+            Self::Shape(shape)=>shape.pipeline(),
+            Self::Stream{pipeline, ..}=>*pipeline,
+        }
+    }
+
+    /// Can both draws be recorded in the same pass
+    fn same_batch(&self, other: &Self)->bool {
+        match (self, other) {
+            (Self::Shape(a), Self::Shape(b))=>a.same_batch(b),
+            _=>self.pipeline() == other.pipeline() && self.pipeline() != Pipeline2D::TexturePoly,
+        }
+    }
+}
+
+/// A draw waiting to be recorded
+struct Draw2D {
+    source: DrawSource,
+    blend: Blend,
+    /// Index of the first transform and tint in the frame's buffers
+    first_instance: u32,
+    instance_count: u32,
+    clip: ClipRect,
+    /// Draws with a higher layer are drawn on top
+    layer: i32,
+    /// Drawn before the blended draws with depth testing. See [`RenderFrame::set_opaque`].
+    opaque: bool,
+}
+
+/// Storage buffers with the data of each instance in a flush
+#[derive(Copy, Clone)]
+struct InstanceNodes {
+    transforms: BufferNode,
+    tints: BufferNode,
+    /// The depth of each instance's layer. Only tested when the flush has opaque draws.
+    depths: BufferNode,
+}
+
+/// Opaque 2D draws write the depth of their layer. Equal depths pass so that later draws on a
+/// layer still cover earlier ones.
+const DEPTH_OPAQUE_2D: DepthStencilMode = DepthStencilMode {
+    compare_op: vk::CompareOp::LESS_OR_EQUAL,
+    bounds_test: false,
+    ..DepthStencilMode::DEPTH_WRITE
+};
+/// Blended 2D draws are hidden by opaque draws on higher layers, but don't hide anything
+const DEPTH_BLENDED_2D: DepthStencilMode = DepthStencilMode {
+    depth_write: false,
+    ..DEPTH_OPAQUE_2D
+};
+
+/// Put opaque draws first and front to back, and the rest back to front. The sort is stable, so
+/// draws on the same layer keep their order.
+fn sort_draws(draws: &mut [Draw2D]) {
+    draws.sort_by_key(|draw|match draw.opaque {
+        true=>(0, -(draw.layer as i64)),
+        false=>(1, draw.layer as i64),
+    });
+}
+
+/// The depth of each instance in `draws`. Each layer gets an evenly spaced depth between 0 and
+/// 1, and higher layers are closer.
+fn layer_depths(draws: &[Draw2D], instance_count: usize)->Vec<f32> {
+    let mut layers = draws.iter().map(|draw|draw.layer).collect::<Vec<_>>();
+    layers.sort_unstable();
+    layers.dedup();
+
+    let mut depths = vec![0.0; instance_count];
+    for draw in draws {
+        let index = layers.binary_search(&draw.layer).unwrap();
+        let depth = (layers.len() - index) as f32 / (layers.len() + 1) as f32;
+        let first = draw.first_instance as usize;
+        depths[first..first + draw.instance_count as usize].fill(depth);
+    }
+
+    return depths;
+}
+
+/// One draw call in a batch
+struct BatchDraw {
+    vertices: BufferNode,
+    index: Option<BufferNode>,
+    count: u32,
+    first_vertex: u32,
+    first_index: u32,
+    first_instance: u32,
+    instance_count: u32,
+    clip: ClipRect,
+}
 
+/// The stream buffers of one flush
+#[derive(Copy, Clone)]
+struct StreamNodes {
+    vertices: Option<BufferNode>,
+    /// Vertices in the [`RoundedRect`] vertex format
+    rects: Option<BufferNode>,
+    indices: Option<BufferNode>,
+}
+
+/// A pool of host visible buffers that are reused between frames for data that changes every
+/// frame. A buffer is only written when nothing else holds it, so buffers still used by frames in
+/// flight are left alone until the GPU is done with them.
+#[derive(Default)]
+pub struct StreamBuffers {
+    pub buffers: Vec<Arc<Buffer>>,
+}
+impl StreamBuffers {
+    /// The smallest buffer that is allocated
+    pub const MIN_SIZE: u64 = 64 * 1024;
+    const USAGE: vk::BufferUsageFlags = vk::BufferUsageFlags::from_raw(
+        vk::BufferUsageFlags::VERTEX_BUFFER.as_raw()
+            | vk::BufferUsageFlags::INDEX_BUFFER.as_raw()
+            | vk::BufferUsageFlags::STORAGE_BUFFER.as_raw()
+    );
+
+    /// Copy `data` into a free buffer that is big enough, or a new one if there isn't one
+    pub fn write(&mut self, device: &Arc<Device>, data: &[u8])->Result<Arc<Buffer>> {
+        for buffer in self.buffers.iter_mut() {
+            let Some(inner) = Arc::get_mut(buffer) else {continue};
+            if inner.info.size < data.len() as u64 {
+                continue;
+            }
+            Buffer::copy_from_slice(inner, 0, data);
+            return Ok(buffer.clone());
+        }
+
+        let size = (data.len() as u64).next_power_of_two().max(Self::MIN_SIZE);
+        debug!("Adding a {size} byte stream buffer");
+        let mut buffer = Buffer::create(device, BufferInfo::host_mem(size, Self::USAGE))?;
+        Buffer::copy_from_slice(&mut buffer, 0, data);
+        let buffer = Arc::new(buffer);
+        self.buffers.push(buffer.clone());
+
+        return Ok(buffer);
+    }
+}
+
+/// How a 2D shape is composited onto what is already drawn
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Blend {
+    /// Colors are blended by their alpha
+    #[default]
+    Alpha,
+    /// Like [`Blend::Alpha`], but the colors are already multiplied by their alpha
+    PremultipliedAlpha,
+    /// The color (multiplied by its alpha) is added to the destination
+    Additive,
+    /// The destination is multiplied by the color. Alpha is ignored.
+    Multiply,
+}
+impl Blend {
+    pub const ALL: [Self;4] = [
+        Self::Alpha,
+        Self::PremultipliedAlpha,
+        Self::Additive,
+        Self::Multiply,
+    ];
+
+    pub fn mode(self)->BlendMode {
+        match self {
+            Self::Alpha=>BlendMode::ALPHA,
+            Self::PremultipliedAlpha=>BlendMode {
+                src_color_blend_factor: vk::BlendFactor::ONE,
+                dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                src_alpha_blend_factor: vk::BlendFactor::ONE,
+                dst_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                ..BlendMode::ALPHA
+            },
+            Self::Additive=>BlendMode {
+                src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+                dst_color_blend_factor: vk::BlendFactor::ONE,
+                src_alpha_blend_factor: vk::BlendFactor::ZERO,
+                dst_alpha_blend_factor: vk::BlendFactor::ONE,
+                ..BlendMode::ALPHA
+            },
+            Self::Multiply=>BlendMode {
+                src_color_blend_factor: vk::BlendFactor::DST_COLOR,
+                dst_color_blend_factor: vk::BlendFactor::ZERO,
+                src_alpha_blend_factor: vk::BlendFactor::ZERO,
+                dst_alpha_blend_factor: vk::BlendFactor::ONE,
+                ..BlendMode::ALPHA
+            },
+        }
+    }
+}
+
+/// The 2D pipelines, without their blend mode
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Pipeline2D {
+    ColorPoly,
+    TexturePoly,
+    /// Signed distance field rectangles. See [`RoundedRect`].
+    RoundedRect,
+}
 
 pub struct State2D {
-    pub line: Arc<GraphicPipeline>,
-    pub color_poly: Arc<GraphicPipeline>,
-    pub tex_poly: Arc<GraphicPipeline>,
+    /// One pipeline for each [`Pipeline2D`] and [`Blend`] mode
+    pub pipelines: fnv::FnvHashMap<(Pipeline2D, Blend), Arc<GraphicPipeline>>,
 
     pub shapes: IdMap<(Shape2DInternal, Shape2D)>,
+    /// The blend mode of each shape. Shapes that aren't in here use [`Blend::default`].
+    pub blends: IdMap<Blend>,
+    /// Buffers for the immediate geometry, transforms and tints of each frame
+    pub stream: StreamBuffers,
 }
 impl State2D {
     pub fn new(device: &Arc<Device>)->Result<Self> {
-        let line = line_shaders()?
-            .line_pipeline(&device)?;
-        let color_poly = color_poly2_shaders()?
-            .polygon_pipeline(&device)?;
-        let tex_poly = tex_poly2_shaders()?
-            .polygon_pipeline(&device)?;
+        let color_poly = color_poly2_shaders()?;
+        let tex_poly = tex_poly2_shaders()?;
+        let rounded_rect = rounded_rect_shaders()?;
+
+        let mut pipelines = fnv::FnvHashMap::default();
+        for blend in Blend::ALL {
+            let mode = blend.mode();
+            pipelines.insert((Pipeline2D::ColorPoly, blend), Arc::new(color_poly.polygon_pipeline(device, mode)?));
+            pipelines.insert((Pipeline2D::TexturePoly, blend), Arc::new(tex_poly.polygon_pipeline(device, mode)?));
+            pipelines.insert((Pipeline2D::RoundedRect, blend), Arc::new(rounded_rect.polygon_pipeline(device, mode)?));
+        }
 
         return Ok(State2D {
-            line: Arc::new(line),
-            color_poly: Arc::new(color_poly),
-            tex_poly: Arc::new(tex_poly),
+            pipelines,
+            shapes: IdMap::default(),
+            blends: IdMap::default(),
+            stream: StreamBuffers::default(),
+        });
+    }
+
+    #[inline]
+    pub fn pipeline(&self, pipeline: Pipeline2D, blend: Blend)->&Arc<GraphicPipeline> {
+        &self.pipelines[&(pipeline, blend)]
+    }
+}
+
+pub struct State3D {
+    pub color_poly: Arc<GraphicPipeline>,
+    pub tex_poly: Arc<GraphicPipeline>,
+
+    pub shapes: IdMap<(Shape3Internal, Shape3)>,
+}
+impl State3D {
+    pub fn new(device: &Arc<Device>)->Result<Self> {
+        let color_poly = color_poly3_shaders()?;
+        let tex_poly = tex_poly3_shaders()?;
+
+        return Ok(State3D {
+            color_poly: Arc::new(color_poly.polygon_pipeline(device, Blend::Alpha.mode())?),
+            tex_poly: Arc::new(tex_poly.polygon_pipeline(device, Blend::Alpha.mode())?),
             shapes: IdMap::default(),
         });
     }
@@ -112,6 +415,18 @@ pub struct Renderer {
 
     /// Data to process 2D shapes
     pub d2: State2D,
+    /// The camera each frame starts with. The viewport is kept up to date by
+    /// [`Renderer::on_resize_event`].
+    pub camera: Camera2D,
+
+    /// Data to process 3D shapes
+    pub d3: State3D,
+    /// The 3D camera each frame starts with. Its viewport is kept up to date like
+    /// [`Renderer::camera`].
+    pub camera3d: Camera3D,
+    /// Depth buffer for 3D shapes. Recreated with the swapchain, and `None` while the window has
+    /// no area.
+    pub depth: Option<Arc<Image>>,
 
     pub display: Display,
     pub display_pool: HashPool,
@@ -121,6 +436,7 @@ pub struct Renderer {
 impl Renderer {
     pub const DEFAULT_IMG_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
     pub const DEFAULT_CLR_SPACE: vk::ColorSpaceKHR = vk::ColorSpaceKHR::SRGB_NONLINEAR;
+    pub const DEPTH_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
 
 
     pub fn new(el: &ActiveEventLoop, window_title: impl Into<String>)->Result<Self> {
@@ -151,6 +467,9 @@ impl Renderer {
         let display = Display::new(&device, swapchain, DisplayInfo::default())?;
 
         let d2 = State2D::new(&device)?;
+        let d3 = State3D::new(&device)?;
+        let depth = create_depth_image(&device, win_size.width, win_size.height)?;
+        let viewport = Vec2::new(win_size.width as f32, win_size.height as f32);
 
         return Ok(Renderer {
             display_pool: HashPool::new(&device),
@@ -159,34 +478,136 @@ impl Renderer {
             display,
 
             d2,
+            camera: Camera2D::pixels(viewport),
+            d3,
+            camera3d: Camera3D::perspective(Point3::new(0.0, 0.0, 5.0), Point3::zero(), viewport),
+            depth,
 
             images: IdMap::default(),
         });
     }
 
+    /// Upload a shape. Errors if it has no triangles, like a line with a width of zero.
     pub fn add_shape2d(&mut self, shape: Shape2D)->Result<ShapeID> {
         let id = crate::new_uuid();
-        match &shape {
-            Shape2D::Line(colors, points)=>{
-                let vertex_color = points.iter().copied()
-                    .zip(colors.iter().copied())
-                    .map(|(v,col)|[v.x,v.y,col.r,col.g,col.b,col.a].into_iter())
-                    .flatten()
-                    .collect::<Vec<f32>>();
-                let vertex_color = Arc::new(Buffer::create_from_slice(
-                    &self.device,
-                    vk::BufferUsageFlags::VERTEX_BUFFER,
-                    bytemuck::cast_slice(vertex_color.as_slice()),
-                )?);
-                let shape_internal = Shape2DInternal::Line(vertex_color, points.len() as u32);
+        let shape_internal = self.build_shape2d(&shape, None)?;
+        self.d2.shapes.insert(id, (shape_internal, shape));
 
-                self.d2.shapes.insert(id, (shape_internal, shape));
+        trace!("Added shape with id: {id}");
+        return Ok(ShapeID(id));
+    }
+
+    /// Replace the geometry of an existing shape. The GPU buffers are reused if they are big
+    /// enough and aren't being used by a frame in flight, otherwise new ones are made. The shape
+    /// can change type. If this fails, then the shape is left as it was.
+    pub fn update_shape2d(&mut self, id: ShapeID, shape: Shape2D)->Result<()> {
+        // Take the shape out of the map so its buffers can be written in place
+        let Some((mut old, old_shape)) = self.d2.shapes.remove(&id.0) else {bail!("Shape with ID `{id:?}` not found")};
+        match self.build_shape2d(&shape, Some(&mut old)) {
+            Ok(shape_internal)=>{
+                self.d2.shapes.insert(id.0, (shape_internal, shape));
+                return Ok(());
+            },
+            Err(e)=>{
+                self.d2.shapes.insert(id.0, (old, old_shape));
+                return Err(e);
             },
+        }
+    }
+
+    /// Upload a shape's vertices and indices, reusing the buffers from `old` when possible. `old`
+    /// is only written to once nothing else can fail.
+    fn build_shape2d(&self, shape: &Shape2D, old: Option<&mut Shape2DInternal>)->Result<Shape2DInternal> {
+        if let Shape2D::Line{colors, points, style} = shape {
+            let mesh = stroke(points, colors, style, false)?;
+            if mesh.is_empty() {
+                bail!("Line has nothing to draw, its width is zero or it has no length");
+            }
+            return self.build_shape2d(&mesh.into_shape(), old);
+        }
+
+        let (old_vertices, old_index) = match old {
+            Some(Shape2DInternal::ColorPoly{vertex_color, index, ..})=>(Some(vertex_color), Some(index)),
+            Some(Shape2DInternal::TexturePoly{vert_uv, index, ..})=>(Some(vert_uv), Some(index)),
+            None=>(None, None),
+        };
+
+        match shape {
+            Shape2D::Line{..}=>unreachable!("Lines are tessellated above"),
             Shape2D::ColorPolygon{colors, vertices, indices}=>{
+                if vertices.is_empty() || indices.is_empty() {
+                    bail!("Shape has no triangles");
+                }
+                let vertex_color = vertices.iter().copied()
+                    .zip(colors.iter().copied())
+                    .flat_map(|(v,col)|[v.x,v.y,col.r,col.g,col.b,col.a])
+                    .collect::<Vec<f32>>();
+                let index_data = bytemuck::cast_slice(indices.as_slice());
+                let vertex_data = bytemuck::cast_slice(vertex_color.as_slice());
+                let index = self.prepare_buffer(old_index, vk::BufferUsageFlags::INDEX_BUFFER, index_data)?;
+                let vertex_color = self.prepare_buffer(old_vertices, vk::BufferUsageFlags::VERTEX_BUFFER, vertex_data)?;
+
+                return Ok(Shape2DInternal::ColorPoly {
+                    index: index.finish(index_data),
+                    vertex_color: vertex_color.finish(vertex_data),
+                    index_count: indices.len() as u32,
+                });
+            },
+            Shape2D::TexturePolygon{texture, vertices, uvs, indices}=>{
+                if vertices.is_empty() || indices.is_empty() {
+                    bail!("Shape has no triangles");
+                }
+                let Some(texture) = self.images.get(&texture.0) else {bail!("Texture does not exist")};
+                let vert_uv = vertices.iter().copied()
+                    .zip(uvs.iter().copied())
+                    .flat_map(|(v,uv)|[v.x,v.y,uv.x,uv.y])
+                    .collect::<Vec<f32>>();
+                let index_data = bytemuck::cast_slice(indices.as_slice());
+                let vertex_data = bytemuck::cast_slice(vert_uv.as_slice());
+                let index = self.prepare_buffer(old_index, vk::BufferUsageFlags::INDEX_BUFFER, index_data)?;
+                let vert_uv = self.prepare_buffer(old_vertices, vk::BufferUsageFlags::VERTEX_BUFFER, vertex_data)?;
+
+                return Ok(Shape2DInternal::TexturePoly {
+                    index: index.finish(index_data),
+                    vert_uv: vert_uv.finish(vertex_data),
+                    index_count: indices.len() as u32,
+                    texture: texture.clone(),
+                });
+            },
+        }
+    }
+
+    /// Pick `old` to write `data` into if nothing else holds it (like a frame still on the GPU)
+    /// and it is big enough with the same usage. Otherwise a new buffer is made with `data`. The
+    /// old buffer isn't written to until [`BufferUpload::finish`].
+    fn prepare_buffer<'a>(&self, old: Option<&'a mut Arc<Buffer>>, usage: vk::BufferUsageFlags, data: &[u8])->Result<BufferUpload<'a>> {
+        // The check `Arc::get_mut` does. Calling it here would keep `old` borrowed past the return.
+        let unique = |b: &Arc<Buffer>|Arc::strong_count(b) == 1 && Arc::weak_count(b) == 0;
+        if let Some(old) = old.filter(|b|b.info.size >= data.len() as u64 && b.info.usage == usage && unique(b)) {
+            return Ok(BufferUpload::InPlace(old));
+        }
+
+        return Ok(BufferUpload::New(Arc::new(Buffer::create_from_slice(&self.device, usage, data)?)));
+    }
+
+    pub fn add_shape3(&mut self, shape: Shape3)->Result<ShapeID> {
+        let id = crate::new_uuid();
+        let shape_internal = self.build_shape3(&shape)?;
+        self.d3.shapes.insert(id, (shape_internal, shape));
+
+        trace!("Added 3D shape with id: {id}");
+        return Ok(ShapeID(id));
+    }
+
+    fn build_shape3(&self, shape: &Shape3)->Result<Shape3Internal> {
+        match shape {
+            Shape3::ColorPolygon{colors, vertices, indices}=>{
+                if vertices.is_empty() || indices.is_empty() {
+                    bail!("Shape has no triangles");
+                }
                 let vertex_color = vertices.iter().copied()
                     .zip(colors.iter().copied())
-                    .map(|(v,col)|[v.x,v.y,col.r,col.g,col.b,col.a].into_iter())
-                    .flatten()
+                    .flat_map(|(v,col)|[v.x,v.y,v.z,col.r,col.g,col.b,col.a])
                     .collect::<Vec<f32>>();
                 let index = Arc::new(Buffer::create_from_slice(
                     &self.device,
@@ -198,20 +619,21 @@ impl Renderer {
                     vk::BufferUsageFlags::VERTEX_BUFFER,
                     bytemuck::cast_slice(vertex_color.as_slice()),
                 )?);
-                let shape_internal = Shape2DInternal::ColorPoly {
+
+                return Ok(Shape3Internal::ColorPoly {
                     index,
                     vertex_color,
                     index_count: indices.len() as u32,
-                };
-
-                self.d2.shapes.insert(id, (shape_internal, shape));
+                });
             },
-            Shape2D::TexturePolygon{texture, vertices, uvs, indices}=>{
+            Shape3::TexturePolygon{texture, vertices, uvs, indices}=>{
+                if vertices.is_empty() || indices.is_empty() {
+                    bail!("Shape has no triangles");
+                }
                 let Some(texture) = self.images.get(&texture.0) else {bail!("Texture does not exist")};
                 let vert_uv = vertices.iter().copied()
                     .zip(uvs.iter().copied())
-                    .map(|(v,uv)|[v.x,v.y,uv.x,uv.y].into_iter())
-                    .flatten()
+                    .flat_map(|(v,uv)|[v.x,v.y,v.z,uv.x,uv.y])
                     .collect::<Vec<f32>>();
                 let index = Arc::new(Buffer::create_from_slice(
                     &self.device,
@@ -223,19 +645,19 @@ impl Renderer {
                     vk::BufferUsageFlags::VERTEX_BUFFER,
                     bytemuck::cast_slice(vert_uv.as_slice()),
                 )?);
-                let shape_internal = Shape2DInternal::TexturePoly {
+
+                return Ok(Shape3Internal::TexturePoly {
                     index,
                     vert_uv,
                     index_count: indices.len() as u32,
                     texture: texture.clone(),
-                };
-
-                self.d2.shapes.insert(id, (shape_internal, shape));
+                });
             },
         }
+    }
 
-        trace!("Added shape with id: {id}");
-        return Ok(ShapeID(id));
+    pub fn drop_shape3(&mut self, id: ShapeID) {
+        self.d3.shapes.remove(&id.0);
     }
 
     pub fn drop_image(&mut self, id: ImageID) {
@@ -244,6 +666,12 @@ impl Renderer {
 
     pub fn drop_shape2d(&mut self, id: ShapeID) {
         self.d2.shapes.remove(&id.0);
+        self.d2.blends.remove(&id.0);
+    }
+
+    /// Set how a shape is blended whenever it is drawn
+    pub fn set_shape_blend(&mut self, id: ShapeID, blend: Blend) {
+        self.d2.blends.insert(id.0, blend);
     }
 
     #[inline]
@@ -259,13 +687,27 @@ impl Renderer {
         trace!("Start a render pass");
 
         return Ok(RenderFrame {
+            camera: self.camera,
+            camera3d: self.camera3d,
+            depth_node: None,
+            depth_clear: true,
             graph,
             swapchain_node: sc_node,
             renderer: self,
 
-            line_count: 0,
-            clr_poly_count: 0,
-            tex_poly_count: 0,
+            clip_stack: Vec::new(),
+            blend: None,
+            layer: 0,
+            opaque: false,
+            draws: Vec::new(),
+            transforms: Vec::new(),
+            tints: Vec::new(),
+            stream_vertices: Vec::new(),
+            rect_vertices: Vec::new(),
+            stream_indices: Vec::new(),
+
+            draw_count: 0,
+            pass_count: 0,
         });
     }
 
@@ -275,6 +717,16 @@ impl Renderer {
         sc_info.width = size.width;
         sc_info.height = size.height;
         self.display.set_swapchain_info(sc_info);
+        self.camera.viewport = Vec2::new(size.width as f32, size.height as f32);
+        self.camera3d.viewport = self.camera.viewport;
+
+        match create_depth_image(&self.device, size.width, size.height) {
+            Ok(depth)=>self.depth = depth,
+            Err(e)=>{
+                error!("Could not resize the depth buffer: {e}");
+                self.depth = None;
+            },
+        }
     }
 
     #[inline]
@@ -324,20 +776,356 @@ pub struct ImageID(pub Uuid);
 #[repr(transparent)]
 pub struct ShapeID(pub Uuid);
 
+/// A 2D camera. It maps world coordinates to the screen: `position` is shown at `origin` (a
+/// fraction of the viewport), scaled by `zoom` and rotated by `rotation` around that point. With
+/// the defaults from [`Camera2D::pixels`], world coordinates are window pixels with the origin in
+/// the top left and Y going down.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Camera2D {
+    /// The world point shown at `origin`
+    pub position: Point2,
+    pub zoom: f32,
+    /// Rotation of the view in radians
+    pub rotation: f32,
+    /// Size of the render target in pixels
+    pub viewport: Vec2,
+    /// Where `position` is on the screen. `(0, 0)` is the top left and `(1, 1)` is the bottom right.
+    pub origin: Vec2,
+}
+impl Camera2D {
+    /// World coordinates are pixels with the origin in the top left
+    pub fn pixels(viewport: Vec2)->Self {
+        Camera2D {
+            position: Point2::zero(),
+            zoom: 1.0,
+            rotation: 0.0,
+            viewport,
+            origin: Vec2::zero(),
+        }
+    }
+
+    /// A camera looking at `position` from the center of the viewport
+    pub fn centered(position: Point2, viewport: Vec2)->Self {
+        Camera2D {
+            position,
+            origin: Vec2::broadcast(0.5),
+            ..Self::pixels(viewport)
+        }
+    }
+
+    /// The matrix from world coordinates to Vulkan clip space
+    pub fn projection(&self)->Mat3 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let scale = Vec2::new(2.0 / self.viewport.x.max(1.0), 2.0 / self.viewport.y.max(1.0)) * self.zoom;
+        let x_axis = Vec2::new(cos, -sin) * scale;
+        let y_axis = Vec2::new(sin, cos) * scale;
+        let offset = self.origin * 2.0 - Vec2::one() - x_axis * self.position.x - y_axis * self.position.y;
+
+        return Mat3::new(
+            Vec3::new(x_axis.x, x_axis.y, 0.0),
+            Vec3::new(y_axis.x, y_axis.y, 0.0),
+            Vec3::new(offset.x, offset.y, 1.0),
+        );
+    }
+
+    /// Convert a world point to window pixels
+    pub fn world_to_screen(&self, point: Point2)->Point2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let p = (point - self.position) * self.zoom;
+        let rotated = Vec2::new(cos * p.x + sin * p.y, cos * p.y - sin * p.x);
+        return rotated + self.origin * self.viewport;
+    }
+
+    /// Convert window pixels to a world point
+    pub fn screen_to_world(&self, point: Point2)->Point2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let p = (point - self.origin * self.viewport) / self.zoom;
+        let rotated = Vec2::new(cos * p.x - sin * p.y, sin * p.x + cos * p.y);
+        return rotated + self.position;
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection3 {
+    /// `fov_y` is the vertical field of view in radians
+    Perspective {fov_y: f32},
+    /// `height` is how many world units fit vertically in the viewport
+    Orthographic {height: f32},
+}
+
+/// A right handed, Y up camera for 3D shapes
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Camera3D {
+    pub position: Point3,
+    /// The point the camera looks at
+    pub target: Point3,
+    pub up: Vec3,
+    pub projection: Projection3,
+    /// Distance to the near clip plane. Must be more than zero.
+    pub near: f32,
+    pub far: f32,
+    /// Size of the render target in pixels
+    pub viewport: Vec2,
+}
+impl Camera3D {
+    /// A 60 degree perspective camera at `position` looking at `target`
+    pub fn perspective(position: Point3, target: Point3, viewport: Vec2)->Self {
+        Camera3D {
+            position,
+            target,
+            up: Vec3::unit_y(),
+            projection: Projection3::Perspective {fov_y: 60f32.to_radians()},
+            near: 0.1,
+            far: 1000.0,
+            viewport,
+        }
+    }
+
+    /// An orthographic camera at `position` looking at `target` that shows `height` world units
+    /// vertically
+    pub fn orthographic(position: Point3, target: Point3, height: f32, viewport: Vec2)->Self {
+        Camera3D {
+            projection: Projection3::Orthographic {height},
+            ..Self::perspective(position, target, viewport)
+        }
+    }
+
+    #[inline]
+    pub fn aspect_ratio(&self)->f32 {
+        self.viewport.x.max(1.0) / self.viewport.y.max(1.0)
+    }
+
+    /// The matrix from world coordinates to camera space
+    #[inline]
+    pub fn view(&self)->Mat4 {
+        Mat4::look_at(self.position, self.target, self.up)
+    }
+
+    /// The matrix from camera space to Vulkan clip space
+    pub fn projection(&self)->Mat4 {
+        use uv::projection::rh_yup::{
+            perspective_vk,
+            orthographic_vk,
+        };
+
+        match self.projection {
+            Projection3::Perspective{fov_y}=>perspective_vk(fov_y, self.aspect_ratio(), self.near, self.far),
+            Projection3::Orthographic{height}=>{
+                let half_height = height * 0.5;
+                let half_width = half_height * self.aspect_ratio();
+                orthographic_vk(-half_width, half_width, -half_height, half_height, self.near, self.far)
+            },
+        }
+    }
+
+    /// The matrix from world coordinates to Vulkan clip space
+    #[inline]
+    pub fn view_projection(&self)->Mat4 {
+        self.projection() * self.view()
+    }
+}
+
+/// A rectangle in swapchain pixels that drawing is limited to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ClipRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+impl ClipRect {
+    pub const fn new(x: i32, y: i32, width: u32, height: u32)->Self {
+        ClipRect {x, y, width, height}
+    }
+
+    /// The overlap of two rectangles. It has no area if they don't overlap.
+    pub fn intersect(&self, other: &Self)->Self {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width as i32).min(other.x + other.width as i32);
+        let bottom = (self.y + self.height as i32).min(other.y + other.height as i32);
+
+        return ClipRect {
+            x,
+            y,
+            width: (right - x).max(0) as u32,
+            height: (bottom - y).max(0) as u32,
+        };
+    }
+
+    #[inline]
+    pub fn is_empty(&self)->bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+/// Is a rectangle of this size big enough to draw. This is `false` for NaN sizes.
+#[inline]
+fn has_area(size: Vec2)->bool {
+    size.x > 0.0 && size.y > 0.0
+}
+
+/// A shadow cast by a [`RoundedRect`], like a CSS box shadow
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoxShadow {
+    pub color: Color,
+    pub offset: Vec2,
+    /// How far the edge of the shadow fades out over
+    pub blur: f32,
+    /// How much bigger than the rectangle the shadow is on each side
+    pub spread: f32,
+}
+
+/// A rectangle with rounded corners, a border and a drop shadow, drawn in one draw with
+/// [`RenderFrame::draw_rounded_rect`]. The edges are anti-aliased at any camera zoom.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RoundedRect {
+    pub top_left: Point2,
+    pub size: Vec2,
+    /// Radius of the top left, top right, bottom right and bottom left corners. Each is limited to
+    /// half of the shortest side.
+    pub radii: [f32; 4],
+    pub fill: Color,
+    /// The border is drawn inside of the rectangle
+    pub border_width: f32,
+    pub border_color: Color,
+    pub shadow: Option<BoxShadow>,
+}
+impl RoundedRect {
+    /// How many floats each vertex has
+    const VERTEX_SIZE: usize = 27;
+
+    pub fn new(top_left: Point2, size: Vec2, fill: Color)->Self {
+        RoundedRect {
+            top_left,
+            size,
+            radii: [0.0; 4],
+            fill,
+            border_width: 0.0,
+            border_color: fill,
+            shadow: None,
+        }
+    }
+
+    pub fn with_radius(mut self, radius: f32)->Self {
+        self.radii = [radius; 4];
+        return self;
+    }
+
+    pub fn with_radii(mut self, radii: [f32; 4])->Self {
+        self.radii = radii;
+        return self;
+    }
+
+    pub fn with_border(mut self, width: f32, color: Color)->Self {
+        self.border_width = width;
+        self.border_color = color;
+        return self;
+    }
+
+    pub fn with_shadow(mut self, shadow: BoxShadow)->Self {
+        self.shadow = Some(shadow);
+        return self;
+    }
+
+    /// The area the rectangle and its shadow can draw to, with room for anti-aliasing
+    pub fn bounds(&self)->(Point2, Point2) {
+        let mut min = self.top_left;
+        let mut max = self.top_left + self.size;
+        if let Some(shadow) = self.shadow {
+            let grow = Vec2::broadcast(shadow.spread + shadow.blur * 0.5);
+            min = min.min_by_component(self.top_left + shadow.offset - grow);
+            max = max.max_by_component(self.top_left + self.size + shadow.offset + grow);
+        }
+
+        return (min - Vec2::one(), max + Vec2::one());
+    }
+
+    /// The 4 vertices of the quad covering [`RoundedRect::bounds`]
+    fn vertices(&self)->Vec<f32> {
+        let half_size = self.size * 0.5;
+        let center = self.top_left + half_size;
+        let max_radius = half_size.x.min(half_size.y).max(0.0);
+        let radii = self.radii.map(|r|r.clamp(0.0, max_radius));
+        let shadow = self.shadow.unwrap_or(BoxShadow {
+            color: Color(0.0, 0.0, 0.0, 0.0),
+            offset: Vec2::zero(),
+            blur: 0.0,
+            spread: 0.0,
+        });
+
+        let (min, max) = self.bounds();
+        let mut vertices = Vec::with_capacity(Self::VERTEX_SIZE * 4);
+        for corner in [min, Point2::new(max.x, min.y), Point2::new(min.x, max.y), max] {
+            let local = corner - center;
+            let fill = self.fill;
+            let border = self.border_color;
+            let shadow_color = shadow.color;
+            vertices.extend([
+                corner.x, corner.y,
+                local.x, local.y,
+                half_size.x, half_size.y,
+                radii[0], radii[1], radii[2], radii[3],
+                fill.r, fill.g, fill.b, fill.a,
+                border.r, border.g, border.b, border.a,
+                self.border_width,
+                shadow_color.r, shadow_color.g, shadow_color.b, shadow_color.a,
+                shadow.offset.x, shadow.offset.y,
+                shadow.blur,
+                shadow.spread,
+            ]);
+        }
+
+        return vertices;
+    }
+}
+
 pub struct RenderFrame<'render> {
     pub renderer: &'render mut Renderer,
     pub graph: RenderGraph,
     pub swapchain_node: SwapchainImageNode,
-
-    pub line_count: usize,
-    pub clr_poly_count: usize,
-    pub tex_poly_count: usize,
+    /// Shape transforms are combined with this camera's projection when they are drawn. Starts
+    /// as [`Renderer::camera`].
+    pub camera: Camera2D,
+    /// Camera for 3D shapes. Starts as [`Renderer::camera3d`].
+    pub camera3d: Camera3D,
+    /// The depth buffer, bound the first time it is used in the frame
+    depth_node: Option<ImageNode>,
+    /// The next pass that uses the depth buffer has to clear it
+    depth_clear: bool,
+    /// Every shape is clipped to the last rectangle. See [`RenderFrame::push_clip`].
+    pub clip_stack: Vec<ClipRect>,
+    /// Overrides the blend mode of every shape drawn while it is set
+    pub blend: Option<Blend>,
+    /// The layer of the following 2D draws. See [`RenderFrame::set_layer`].
+    pub layer: i32,
+    /// Whether the following 2D draws are opaque. See [`RenderFrame::set_opaque`].
+    pub opaque: bool,
+    /// Shapes waiting for [`RenderFrame::flush`]
+    draws: Vec<Draw2D>,
+    /// One std430 mat3 for each queued instance
+    transforms: Vec<f32>,
+    /// One tint for each queued instance
+    tints: Vec<Color>,
+    /// Immediate geometry in the color vertex format (`x, y, r, g, b, a`)
+    stream_vertices: Vec<f32>,
+    /// Immediate [`RoundedRect`]s in their own vertex format
+    rect_vertices: Vec<f32>,
+    stream_indices: Vec<u16>,
+
+    /// Shapes recorded so far
+    pub draw_count: usize,
+    /// Shape passes recorded so far
+    pub pass_count: usize,
 }
 impl<'render> RenderFrame<'render> {
-    pub fn custom(&mut self, render_fn: fn(&mut Renderer, &mut RenderGraph))->&mut Self {
+    /// Record custom passes. Queued shapes are flushed first so they are drawn before the custom
+    /// passes. This ends the current set of layers (see [`RenderFrame::set_layer`]).
+    pub fn custom(&mut self, render_fn: fn(&mut Renderer, &mut RenderGraph))->Result<&mut Self> {
+        self.flush()?;
         render_fn(self.renderer, &mut self.graph);
 
-        return self;
+        return Ok(self);
     }
 
     #[inline]
@@ -345,91 +1133,517 @@ impl<'render> RenderFrame<'render> {
         self.renderer.upload_image_with_graph(&mut self.graph, img)
     }
 
+    /// Clip every shape drawn after this to `rect` until [`RenderFrame::pop_clip`] is called. The
+    /// rectangle is intersected with the current clip rectangle (and the swapchain image), so
+    /// nested clips never draw outside of their parents.
+    pub fn push_clip(&mut self, rect: ClipRect)->&mut Self {
+        let bounds = self.clip_rect();
+        self.clip_stack.push(bounds.intersect(&rect));
+
+        return self;
+    }
+
+    /// Go back to the clip rectangle from before the last [`RenderFrame::push_clip`]
+    pub fn pop_clip(&mut self)->&mut Self {
+        if self.clip_stack.pop().is_none() {
+            warn!("Popped a clip rectangle from an empty clip stack");
+        }
+
+        return self;
+    }
+
+    /// Draw the following shapes with `camera`
+    pub fn set_camera(&mut self, camera: Camera2D)->&mut Self {
+        self.camera = camera;
+        return self;
+    }
+
+    /// Put the following 2D draws on `layer`. Higher layers are drawn on top of lower ones no
+    /// matter the order they are queued in, and draws on the same layer keep their order. The
+    /// layer starts at `0` each frame.
+    ///
+    /// Layers only order the draws between two flushes. [`RenderFrame::flush`],
+    /// [`RenderFrame::shape3d`] and [`RenderFrame::custom`] draw everything queued so far, so 2D
+    /// draws queued after them are on top even if their layer is lower.
+    pub fn set_layer(&mut self, layer: i32)->&mut Self {
+        self.layer = layer;
+        return self;
+    }
+
+    /// Mark the following 2D draws as opaque. Opaque draws are ordered with the depth buffer
+    /// instead of painting over each other: they are drawn front to back before the rest of the
+    /// draws of a flush, so covered pixels are only shaded once. Within a layer, opaque draws end
+    /// up below the blended ones. Their depth is lost to later 3D shapes.
+    pub fn set_opaque(&mut self, opaque: bool)->&mut Self {
+        self.opaque = opaque;
+        return self;
+    }
+
+    /// Draw the following 3D shapes with `camera`
+    pub fn set_camera3d(&mut self, camera: Camera3D)->&mut Self {
+        self.camera3d = camera;
+        return self;
+    }
+
+    /// Draw the following shapes with `blend` instead of their own blend mode (see
+    /// [`Renderer::set_shape_blend`]). `None` goes back to each shape's blend mode.
+    pub fn set_blend(&mut self, blend: Option<Blend>)->&mut Self {
+        self.blend = blend;
+        return self;
+    }
+
+    /// The rectangle shapes are currently clipped to. This is the whole swapchain image if the
+    /// clip stack is empty.
+    pub fn clip_rect(&self)->ClipRect {
+        if let Some(clip) = self.clip_stack.last() {
+            return *clip;
+        }
+
+        let info = self.renderer.display.swapchain_info();
+        return ClipRect::new(0, 0, info.width, info.height);
+    }
+
+    /// Queue a shape to be drawn. Shapes are recorded in batches when the frame is finished (or
+    /// before [`RenderFrame::custom`]), and are drawn by layer, then in the order they were
+    /// queued. See [`RenderFrame::set_layer`].
+    #[inline]
     pub fn shape2d(&mut self, id: ShapeID, transform: Transform2)->Result<&mut Self> {
+        self.shape2d_instanced(id, &[transform], None)
+    }
+
+    /// Queue a shape with its colors multiplied by `tint`, and its alpha multiplied by `opacity`
+    /// as well. With [`Blend::PremultipliedAlpha`] the color is multiplied by `opacity` too. This
+    /// is cheaper than rebuilding the shape to change its color.
+    pub fn shape2d_tinted(&mut self, id: ShapeID, transform: Transform2, tint: Color, opacity: f32)->Result<&mut Self> {
+        let tint = match self.shape_blend(id) {
+            Blend::PremultipliedAlpha=>Color {
+                r: tint.r * opacity,
+                g: tint.g * opacity,
+                b: tint.b * opacity,
+                a: tint.a * opacity,
+            },
+            _=>Color {
+                a: tint.a * opacity,
+                ..tint
+            },
+        };
+        self.shape2d_instanced(id, &[transform], Some(&[tint]))
+    }
+
+    /// Queue a shape to be drawn once for each transform with a single instanced draw call.
+    /// `tints` is multiplied with the colors of each instance, and must be as long as
+    /// `transforms`.
+    pub fn shape2d_instanced(&mut self, id: ShapeID, transforms: &[Transform2], tints: Option<&[Color]>)->Result<&mut Self> {
         let Some((shape, _)) = self.renderer.d2.shapes.get(&id.0) else {bail!("Shape with ID `{id:?}` not found")};
-        let transform = transform.into_homogeneous_matrix();
+        if let Some(tints) = tints.filter(|tints|tints.len() != transforms.len()) {
+            bail!("Got {} tints for {} instances", tints.len(), transforms.len());
+        }
+        if transforms.is_empty() {
+            return Ok(self);
+        }
 
-        let columns = transform.as_component_array();
+        let clip = self.clip_rect();
+        if clip.is_empty() {
+            trace!("Skip a shape that is clipped away");
+            return Ok(self);
+        }
 
-        let mut bytes: Vec<u8> = Vec::with_capacity(64);
-        bytes.extend(columns[0].as_byte_slice());
-        bytes.extend([0;4]);
-        bytes.extend(columns[1].as_byte_slice());
-        bytes.extend([0;4]);
-        bytes.extend(columns[2].as_byte_slice());
-        bytes.extend([0;4]);
+        let source = DrawSource::Shape(shape.clone());
+        let blend = self.shape_blend(id);
+        let first_instance = self.push_instances(transforms, tints);
+        self.draws.push(Draw2D {
+            source,
+            blend,
+            first_instance,
+            instance_count: transforms.len() as u32,
+            clip,
+            layer: self.layer,
+            opaque: self.opaque,
+        });
 
-        match shape {
-            Shape2DInternal::Line(vertex_color, vertex_count)=>{
-                trace!("Render a line");
-                self.line_count += 1;
-                let mut pass = self.graph
-                    .begin_pass(format!("Line #{}", self.line_count))
-                    .bind_pipeline(&self.renderer.d2.line);
-                let vertex_count = *vertex_count;
-                let cv_node = pass.bind_node(vertex_color);
-                pass
-                    .access_node(cv_node, AccessType::VertexBuffer)
-                    .store_color(0, self.swapchain_node)
-                    .record_subpass(move|sp, _|{
-                        sp.push_constants(&bytes);
-                        sp.bind_vertex_buffer(cv_node);
-                        sp.draw(vertex_count, 1, 0, 0);
-                    })
-                    .submit_pass();
+        return Ok(self);
+    }
+
+    /// The blend a shape is drawn with: the frame's blend if it is set, otherwise the shape's own
+    fn shape_blend(&self, id: ShapeID)->Blend {
+        self.blend
+            .or_else(||self.renderer.d2.blends.get(&id.0).copied())
+            .unwrap_or_default()
+    }
+
+    /// Draw a filled rectangle this frame only. Nothing is drawn if the size isn't positive.
+    pub fn draw_rect(&mut self, top_left: Point2, size: Vec2, color: Color)->&mut Self {
+        if !has_area(size) {
+            return self;
+        }
+
+        let vertices = [
+            top_left,
+            top_left + Vec2::new(size.x, 0.0),
+            top_left + Vec2::new(0.0, size.y),
+            top_left + size,
+        ];
+        self.queue_stream(Pipeline2D::ColorPoly, &vertices, &[color;4], &[0, 1, 2, 3, 2, 1]);
+
+        return self;
+    }
+
+    /// Draw triangles this frame only, like [`Shape2D::ColorPolygon`] but without making a shape
+    pub fn draw_polygon(&mut self, vertices: &[Point2], indices: &[u16], colors: &[Color])->Result<&mut Self> {
+        if colors.len() != vertices.len() {
+            bail!("Got {} colors for {} vertices", colors.len(), vertices.len());
+        }
+        if indices.iter().any(|i|*i as usize >= vertices.len()) {
+            bail!("Polygon index out of range");
+        }
+        self.queue_stream(Pipeline2D::ColorPoly, vertices, colors, indices);
+
+        return Ok(self);
+    }
+
+    /// Draw a rounded rectangle this frame only. The corners, border and shadow are all drawn by
+    /// one quad with a signed distance field, so this is cheap to call every frame.
+    pub fn draw_rounded_rect(&mut self, rect: &RoundedRect)->&mut Self {
+        let clip = self.clip_rect();
+        if clip.is_empty() || !has_area(rect.size) {
+            return self;
+        }
+
+        let first_vertex = (self.rect_vertices.len() / RoundedRect::VERTEX_SIZE) as u32;
+        let first_index = self.stream_indices.len() as u32;
+        self.rect_vertices.extend(rect.vertices());
+        self.stream_indices.extend([0, 1, 2, 2, 1, 3]);
+
+        self.push_stream_draw(
+            DrawSource::Stream {
+                pipeline: Pipeline2D::RoundedRect,
+                first_vertex,
+                vertex_count: 4,
+                first_index,
+                index_count: 6,
+            },
+            clip,
+        );
+
+        return self;
+    }
+
+    /// Draw a line through `points` this frame only, like [`Shape2D::Line`] but without making a
+    /// shape
+    pub fn draw_polyline(&mut self, points: &[Point2], colors: &[Color], style: &LineStyle)->Result<&mut Self> {
+        let mesh = stroke(points, colors, style, false)?;
+        self.queue_stream(Pipeline2D::ColorPoly, &mesh.vertices, &mesh.colors, &mesh.indices);
+
+        return Ok(self);
+    }
+
+    fn queue_stream(&mut self, pipeline: Pipeline2D, vertices: &[Point2], colors: &[Color], indices: &[u16]) {
+        let clip = self.clip_rect();
+        if vertices.is_empty() || indices.is_empty() || clip.is_empty() {
+            return;
+        }
+
+        let first_vertex = (self.stream_vertices.len() / 6) as u32;
+        let first_index = self.stream_indices.len() as u32;
+        self.stream_vertices.extend(vertices.iter()
+            .zip(colors)
+            .flat_map(|(v, c)|[v.x, v.y, c.r, c.g, c.b, c.a])
+        );
+        self.stream_indices.extend_from_slice(indices);
+
+        self.push_stream_draw(
+            DrawSource::Stream {
+                pipeline,
+                first_vertex,
+                vertex_count: vertices.len() as u32,
+                first_index,
+                index_count: indices.len() as u32,
             },
-            Shape2DInternal::ColorPoly{vertex_color, index_count, index}=>{
-                trace!("Render a colored polygon");
-                self.clr_poly_count += 1;
-                let mut pass = self.graph
-                    .begin_pass(format!("ColorPoly #{}", self.clr_poly_count))
-                    .bind_pipeline(&self.renderer.d2.color_poly);
-                let index_count = *index_count;
-                let cv_node = pass.bind_node(vertex_color);
-                let index_node = pass.bind_node(index);
-                pass
-                    .access_node(cv_node, AccessType::VertexBuffer)
-                    .access_node(index_node, AccessType::IndexBuffer)
-                    .store_color(0, self.swapchain_node)
-                    .record_subpass(move|sp, _|{
-                        sp.push_constants(&bytes);
-                        sp.bind_vertex_buffer(cv_node);
-                        sp.bind_index_buffer(index_node, vk::IndexType::UINT16);
-                        sp.draw_indexed(index_count, 1, 0, 0, 0);
-                    })
-                    .submit_pass();
+            clip,
+        );
+    }
+
+    /// Queue a draw of immediate geometry that is already in the stream buffers
+    fn push_stream_draw(&mut self, source: DrawSource, clip: ClipRect) {
+        let first_instance = self.push_instances(&[Transform2::identity()], None);
+        self.draws.push(Draw2D {
+            source,
+            blend: self.blend.unwrap_or_default(),
+            first_instance,
+            instance_count: 1,
+            clip,
+            layer: self.layer,
+            opaque: self.opaque,
+        });
+    }
+
+    /// Add the transforms (combined with the camera) and tints of some instances. Returns the
+    /// index of the first one.
+    fn push_instances(&mut self, transforms: &[Transform2], tints: Option<&[Color]>)->u32 {
+        let first_instance = self.tints.len() as u32;
+        let projection = self.camera.projection();
+        for transform in transforms {
+            let transform = projection * transform.into_homogeneous_matrix();
+            let columns = transform.as_component_array();
+            // Each mat3 column is padded to a vec4 in std430
+            self.transforms.extend([
+                columns[0].x, columns[0].y, columns[0].z, 0.0,
+                columns[1].x, columns[1].y, columns[1].z, 0.0,
+                columns[2].x, columns[2].y, columns[2].z, 0.0,
+            ]);
+        }
+        match tints {
+            Some(tints)=>self.tints.extend_from_slice(tints),
+            None=>self.tints.extend(std::iter::repeat_n(Color::WHITE, transforms.len())),
+        }
+
+        return first_instance;
+    }
+
+    /// Draw a 3D shape. Queued 2D shapes are flushed first, so they stay below it whatever their
+    /// layer. 3D shapes are depth tested against each other, and the depth buffer is cleared on
+    /// the first one of each frame.
+    pub fn shape3d(&mut self, id: ShapeID, transform: Transform3)->Result<&mut Self> {
+        self.flush()?;
+
+        let Some((shape, _)) = self.renderer.d3.shapes.get(&id.0) else {bail!("3D shape with ID `{id:?}` not found")};
+        let Some(depth_image) = self.renderer.depth.clone() else {return Ok(self)};
+        let clip = self.clip_rect();
+        if clip.is_empty() {
+            return Ok(self);
+        }
+
+        let (pipeline, vertices, index, index_count, texture) = match shape.clone() {
+            Shape3Internal::ColorPoly{vertex_color, index, index_count}=>{
+                (self.renderer.d3.color_poly.clone(), vertex_color, index, index_count, None)
             },
-            Shape2DInternal::TexturePoly{vert_uv: vertex_uv, index_count, index, texture}=>{
-                trace!("Render a textured polygon");
-                self.tex_poly_count += 1;
-                let mut pass = self.graph
-                    .begin_pass(format!("TexturePoly #{}", self.tex_poly_count))
-                    .bind_pipeline(&self.renderer.d2.tex_poly);
-                let index_count = *index_count;
-                let cv_node = pass.bind_node(vertex_uv);
-                let index_node = pass.bind_node(index);
-                let texture_node = pass.bind_node(texture);
-                pass
-                    .access_node(cv_node, AccessType::VertexBuffer)
-                    .access_node(index_node, AccessType::IndexBuffer)
-                    .read_descriptor(0, texture_node)
-                    .store_color(0, self.swapchain_node)
-                    .record_subpass(move|sp, _|{
-                        sp.push_constants(&bytes);
-                        sp.bind_vertex_buffer(cv_node);
-                        sp.bind_index_buffer(index_node, vk::IndexType::UINT16);
-                        sp.draw_indexed(index_count, 1, 0, 0, 0);
-                    })
-                    .submit_pass();
+            Shape3Internal::TexturePoly{vert_uv, index, index_count, texture}=>{
+                (self.renderer.d3.tex_poly.clone(), vert_uv, index, index_count, Some(texture))
             },
+        };
+        let mvp = self.camera3d.view_projection() * transform.into_homogeneous_matrix();
+
+        let (depth, clear_depth) = self.depth_node(depth_image);
+
+        self.pass_count += 1;
+        let mut pass = self.graph
+            .begin_pass(format!("Shapes3D #{}", self.pass_count))
+            .bind_pipeline(&pipeline)
+            .set_depth_stencil(DepthStencilMode::DEPTH_WRITE);
+        let vertices = pass.bind_node(vertices);
+        let index = pass.bind_node(index);
+        pass = pass
+            .access_node(vertices, AccessType::VertexBuffer)
+            .access_node(index, AccessType::IndexBuffer);
+        if let Some(texture) = texture {
+            let texture = pass.bind_node(texture);
+            pass = pass.read_descriptor(0, texture);
         }
+        pass = if clear_depth {
+            pass.clear_depth_stencil(depth)
+        } else {
+            pass.load_depth_stencil(depth)
+        };
+
+        self.draw_count += 1;
+        pass
+            .store_depth_stencil(depth)
+            .store_color(0, self.swapchain_node)
+            .record_subpass(move|sp, _|{
+                sp.set_scissor(clip.x, clip.y, clip.width, clip.height);
+                sp.push_constants(mvp.as_byte_slice());
+                sp.bind_vertex_buffer(vertices);
+                sp.bind_index_buffer(index, vk::IndexType::UINT16);
+                sp.draw_indexed(index_count, 1, 0, 0, 0);
+            })
+            .submit_pass();
 
         return Ok(self);
     }
 
+    /// Record every queued shape into the render graph. Runs of shapes that use the same pipeline
+    /// and texture share a pass, and all of the transforms and tints go in storage buffers. The
+    /// shapes are sorted by layer here, so shapes queued after this are drawn on top of them.
+    pub fn flush(&mut self)->Result<&mut Self> {
+        if self.draws.is_empty() {
+            return Ok(self);
+        }
+
+        let mut draws = std::mem::take(&mut self.draws);
+        let transforms = std::mem::take(&mut self.transforms);
+        let tints = std::mem::take(&mut self.tints);
+        let vertices = std::mem::take(&mut self.stream_vertices);
+        let rects = std::mem::take(&mut self.rect_vertices);
+        let indices = std::mem::take(&mut self.stream_indices);
+
+        sort_draws(&mut draws);
+        let depths = layer_depths(&draws, tints.len());
+
+        let instances = InstanceNodes {
+            transforms: self.stream_node(bytemuck::cast_slice(transforms.as_slice()))?.unwrap(),
+            tints: self.stream_node(bytemuck::cast_slice(tints.as_slice()))?.unwrap(),
+            depths: self.stream_node(bytemuck::cast_slice(depths.as_slice()))?.unwrap(),
+        };
+        let stream = StreamNodes {
+            vertices: self.stream_node(bytemuck::cast_slice(vertices.as_slice()))?,
+            rects: self.stream_node(bytemuck::cast_slice(rects.as_slice()))?,
+            indices: self.stream_node(bytemuck::cast_slice(indices.as_slice()))?,
+        };
+
+        // The depth buffer is only needed to order opaque draws. It is cleared by the first
+        // opaque pass, and again by the next 3D shape.
+        let depth = self.renderer.depth.clone()
+            .filter(|_|draws[0].opaque)
+            .map(|image|self.depth_node(image).0);
+        let mut clear_depth = true;
+
+        let batches = draws.chunk_by(|a, b|{
+            a.blend == b.blend && a.opaque == b.opaque && a.source.same_batch(&b.source)
+        });
+        for batch in batches {
+            self.record_batch(batch, instances, stream, depth.map(|depth|(depth, clear_depth)));
+            clear_depth = false;
+        }
+        if depth.is_some() {
+            self.depth_clear = true;
+        }
+        trace!("Recorded {} shapes", draws.len());
+
+        return Ok(self);
+    }
+
+    /// Bind the depth buffer to the graph. Also returns whether it has to be cleared before use.
+    fn depth_node(&mut self, image: Arc<Image>)->(ImageNode, bool) {
+        let node = *self.depth_node.get_or_insert_with(||self.graph.bind_node(image));
+        let clear = std::mem::replace(&mut self.depth_clear, false);
+
+        return (node, clear);
+    }
+
+    /// Write `data` to a stream buffer and bind it to the graph. Returns `None` if there is no
+    /// data.
+    fn stream_node(&mut self, data: &[u8])->Result<Option<BufferNode>> {
+        if data.is_empty() {
+            return Ok(None);
+        }
+        let buffer = self.renderer.d2.stream.write(&self.renderer.device, data)?;
+
+        return Ok(Some(self.graph.bind_node(buffer)));
+    }
+
+    /// Record one pass for a run of draws. `depth` is the depth buffer and whether to clear it, if
+    /// this flush orders opaque draws with it.
+    fn record_batch(&mut self, batch: &[Draw2D], instances: InstanceNodes, stream: StreamNodes, depth: Option<(ImageNode, bool)>) {
+        let pipeline = self.renderer.d2.pipeline(batch[0].source.pipeline(), batch[0].blend);
+
+        self.pass_count += 1;
+        let mut pass = self.graph
+            .begin_pass(format!("Shapes2D #{}", self.pass_count))
+            .bind_pipeline(pipeline);
+        if let Some((depth, clear)) = depth {
+            let mode = if batch[0].opaque {DEPTH_OPAQUE_2D} else {DEPTH_BLENDED_2D};
+            pass = pass.set_depth_stencil(mode);
+            pass = if clear {
+                pass.clear_depth_stencil(depth)
+            } else {
+                pass.load_depth_stencil(depth)
+            };
+            pass = pass.store_depth_stencil(depth);
+        }
+
+        let mut commands = Vec::with_capacity(batch.len());
+        let mut stream_accessed = false;
+        for draw in batch {
+            let command = match &draw.source {
+                DrawSource::Shape(shape)=>{
+                    let (vertices, index, count) = match shape {
+                        Shape2DInternal::ColorPoly{vertex_color, index_count, index}=>(vertex_color, Some(index), *index_count),
+                        Shape2DInternal::TexturePoly{vert_uv, index_count, index, ..}=>(vert_uv, Some(index), *index_count),
+                    };
+                    let vertices = pass.bind_node(vertices);
+                    pass = pass.access_node(vertices, AccessType::VertexBuffer);
+                    let index = index.map(|index|pass.bind_node(index));
+                    if let Some(index) = index {
+                        pass = pass.access_node(index, AccessType::IndexBuffer);
+                    }
+
+                    BatchDraw {
+                        vertices,
+                        index,
+                        count,
+                        first_vertex: 0,
+                        first_index: 0,
+                        first_instance: draw.first_instance,
+                        instance_count: draw.instance_count,
+                        clip: draw.clip,
+                    }
+                },
+                DrawSource::Stream{pipeline, first_vertex, vertex_count, first_index, index_count}=>{
+                    let vertices = match pipeline {
+                        Pipeline2D::RoundedRect=>stream.rects,
+                        _=>stream.vertices,
+                    };
+                    let Some(vertices) = vertices else {continue};
+                    if !stream_accessed {
+                        pass = pass.access_node(vertices, AccessType::VertexBuffer);
+                        if let Some(indices) = stream.indices {
+                            pass = pass.access_node(indices, AccessType::IndexBuffer);
+                        }
+                        stream_accessed = true;
+                    }
+
+                    let indexed = *index_count > 0;
+                    BatchDraw {
+                        vertices,
+                        index: stream.indices.filter(|_|indexed),
+                        count: if indexed {*index_count} else {*vertex_count},
+                        first_vertex: *first_vertex,
+                        first_index: *first_index,
+                        first_instance: draw.first_instance,
+                        instance_count: draw.instance_count,
+                        clip: draw.clip,
+                    }
+                },
+            };
+            commands.push(command);
+        }
+
+        if let DrawSource::Shape(Shape2DInternal::TexturePoly{texture, ..}) = &batch[0].source {
+            let texture = pass.bind_node(texture);
+            pass = pass.read_descriptor(0, texture);
+        }
+
+        self.draw_count += commands.len();
+        pass
+            .access_descriptor(1, instances.transforms, AccessType::VertexShaderReadOther)
+            .access_descriptor(2, instances.tints, AccessType::VertexShaderReadOther)
+            .access_descriptor(3, instances.depths, AccessType::VertexShaderReadOther)
+            .store_color(0, self.swapchain_node)
+            .record_subpass(move|sp, _|{
+                for cmd in commands.iter() {
+                    sp.set_scissor(cmd.clip.x, cmd.clip.y, cmd.clip.width, cmd.clip.height);
+                    sp.bind_vertex_buffer(cmd.vertices);
+                    match cmd.index {
+                        Some(index)=>{
+                            sp.bind_index_buffer(index, vk::IndexType::UINT16);
+                            sp.draw_indexed(cmd.count, cmd.instance_count, cmd.first_index, cmd.first_vertex as i32, cmd.first_instance);
+                        },
+                        None=>{
+                            sp.draw(cmd.count, cmd.instance_count, cmd.first_vertex, cmd.first_instance);
+                        },
+                    }
+                }
+            })
+            .submit_pass();
+    }
+
     /// Finish the render and submit it to the GPU
-    pub fn finish(self)->Result<()> {
+    pub fn finish(mut self)->Result<()> {
         trace!("Finish rendering");
+        self.flush()?;
+        if !self.clip_stack.is_empty() {
+            warn!("Finished a frame with {} clip rectangles still pushed", self.clip_stack.len());
+        }
         self.renderer.window.pre_present_notify();
         self.renderer.display.present_image(
             &mut self.renderer.display_pool,
@@ -448,44 +1662,28 @@ pub struct ShaderInternal {
     pub frag: Shader,
 }
 impl ShaderInternal {
-    /// A line strip pipeline
-    pub fn line_pipeline(self, device: &Arc<Device>)->Result<GraphicPipeline> {
-        self.pipeline(
-            device,
-            GraphicPipelineInfo::builder()
-                .topology(vk::PrimitiveTopology::LINE_STRIP)
-                .polygon_mode(vk::PolygonMode::LINE),
-        )
-    }
-
     /// A filled triangle list pipeline
-    pub fn polygon_pipeline(self, device: &Arc<Device>)->Result<GraphicPipeline> {
+    pub fn polygon_pipeline(&self, device: &Arc<Device>, blend: BlendMode)->Result<GraphicPipeline> {
         self.pipeline(
             device,
             GraphicPipelineInfo::builder()
                 .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-                .polygon_mode(vk::PolygonMode::FILL),
+                .polygon_mode(vk::PolygonMode::FILL)
+                .blend(blend),
         )
     }
 
     /// A new custom pipeline
-    pub fn pipeline(self, device: &Arc<Device>, info: impl Into<GraphicPipelineInfo>)->Result<GraphicPipeline> {
+    pub fn pipeline(&self, device: &Arc<Device>, info: impl Into<GraphicPipelineInfo>)->Result<GraphicPipeline> {
         Ok(GraphicPipeline::create(
             device,
             info,
-            [self.vert, self.frag],
+            [self.vert.clone(), self.frag.clone()],
         )?)
     }
 }
 
 
-fn line_shaders()->Result<ShaderInternal> {
-    translate_shaders(
-        "shaders/line_vert.glsl",
-        "shaders/line_frag.glsl",
-    )
-}
-
 fn color_poly2_shaders()->Result<ShaderInternal> {
     translate_shaders(
         "shaders/color_poly2_vert.glsl",
@@ -500,6 +1698,42 @@ fn tex_poly2_shaders()->Result<ShaderInternal> {
     )
 }
 
+fn rounded_rect_shaders()->Result<ShaderInternal> {
+    translate_shaders(
+        "shaders/rounded_rect_vert.glsl",
+        "shaders/rounded_rect_frag.glsl",
+    )
+}
+
+fn color_poly3_shaders()->Result<ShaderInternal> {
+    translate_shaders(
+        "shaders/color_poly3_vert.glsl",
+        "shaders/color_poly3_frag.glsl",
+    )
+}
+
+fn tex_poly3_shaders()->Result<ShaderInternal> {
+    translate_shaders(
+        "shaders/tex_poly3_vert.glsl",
+        "shaders/tex_poly3_frag.glsl",
+    )
+}
+
+/// A depth buffer the size of the swapchain. Returns `None` if either side is zero.
+fn create_depth_image(device: &Arc<Device>, width: u32, height: u32)->Result<Option<Arc<Image>>> {
+    if width == 0 || height == 0 {
+        return Ok(None);
+    }
+
+    let info = ImageInfo::image_2d(
+        width,
+        height,
+        Renderer::DEPTH_FORMAT,
+        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+    );
+    return Ok(Some(Arc::new(Image::create(device, info)?)));
+}
+
 /// Translates WGSL shader text to SPIR-V binary data for use in a `GraphicsPipeline`
 pub fn translate_shaders(vert_path: &str, frag_path: &str)->Result<ShaderInternal> {
     use shaderc::{
@@ -531,3 +1765,298 @@ pub fn translate_shaders(vert_path: &str, frag_path: &str)->Result<ShaderInterna
 
     return Ok(ShaderInternal {vert, frag});
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Vec2, expected: Vec2) {
+        assert!((actual - expected).mag() < 1e-3, "expected {expected:?}, got {actual:?}");
+    }
+
+    /// Where the projection puts a world point, in window pixels
+    fn projected(camera: &Camera2D, point: Point2)->Point2 {
+        let clip = camera.projection() * Vec3::new(point.x, point.y, 1.0);
+        return (Vec2::new(clip.x, clip.y) + Vec2::one()) * 0.5 * camera.viewport;
+    }
+
+    #[test]
+    fn camera_pixels() {
+        let camera = Camera2D::pixels(Vec2::new(800.0, 600.0));
+        let projection = camera.projection();
+        let corner = |x, y|{
+            let clip = projection * Vec3::new(x, y, 1.0);
+            Vec2::new(clip.x, clip.y)
+        };
+
+        assert_close(corner(0.0, 0.0), Vec2::new(-1.0, -1.0));
+        assert_close(corner(800.0, 0.0), Vec2::new(1.0, -1.0));
+        assert_close(corner(0.0, 600.0), Vec2::new(-1.0, 1.0));
+        assert_close(corner(800.0, 600.0), Vec2::new(1.0, 1.0));
+        assert_close(camera.world_to_screen(Point2::new(12.0, 34.0)), Vec2::new(12.0, 34.0));
+    }
+
+    #[test]
+    fn camera_centered() {
+        let mut camera = Camera2D::centered(Point2::new(100.0, 50.0), Vec2::new(800.0, 600.0));
+        assert_close(camera.world_to_screen(Point2::new(100.0, 50.0)), Vec2::new(400.0, 300.0));
+
+        camera.zoom = 2.0;
+        assert_close(camera.world_to_screen(Point2::new(110.0, 50.0)), Vec2::new(420.0, 300.0));
+        // A quarter turn shows world +X going up the screen
+        camera.rotation = std::f32::consts::FRAC_PI_2;
+        assert_close(camera.world_to_screen(Point2::new(110.0, 50.0)), Vec2::new(400.0, 280.0));
+    }
+
+    #[test]
+    fn camera_round_trip() {
+        let cameras = [
+            Camera2D::pixels(Vec2::new(640.0, 480.0)),
+            Camera2D {
+                position: Point2::new(-30.0, 75.0),
+                zoom: 2.5,
+                rotation: 0.7,
+                viewport: Vec2::new(800.0, 600.0),
+                origin: Vec2::new(0.25, 0.75),
+            },
+            Camera2D {
+                zoom: 0.3,
+                rotation: -2.0,
+                ..Camera2D::centered(Point2::new(1000.0, -500.0), Vec2::new(1920.0, 1080.0))
+            },
+        ];
+        let points = [Point2::zero(), Point2::new(10.0, -20.0), Point2::new(-333.0, 444.0)];
+
+        for camera in cameras {
+            for point in points {
+                let screen = camera.world_to_screen(point);
+                assert_close(camera.screen_to_world(screen), point);
+                assert_close(camera.world_to_screen(camera.screen_to_world(point)), point);
+                // The projection used to draw agrees with the conversions
+                assert_close(projected(&camera, point), screen);
+            }
+        }
+    }
+
+    /// A draw of `instance_count` instances starting at `first_instance`
+    fn draw(first_instance: u32, instance_count: u32, layer: i32, opaque: bool)->Draw2D {
+        Draw2D {
+            source: DrawSource::Stream {
+                pipeline: Pipeline2D::ColorPoly,
+                first_vertex: 0,
+                vertex_count: 3,
+                first_index: 0,
+                index_count: 0,
+            },
+            blend: Blend::Alpha,
+            first_instance,
+            instance_count,
+            clip: ClipRect::new(0, 0, 100, 100),
+            layer,
+            opaque,
+        }
+    }
+
+    #[test]
+    fn draw_order() {
+        // In submission order: (first instance, count, layer, opaque)
+        let mut draws = [
+            draw(0, 1, 0, false),
+            draw(1, 2, 1, true),
+            draw(3, 1, -1, false),
+            draw(4, 1, 0, true),
+            draw(5, 1, 1, false),
+            draw(6, 1, 0, false),
+            draw(7, 1, 1, true),
+        ];
+        sort_draws(&mut draws);
+
+        // Opaque front to back, then blended back to front, in submission order within a layer
+        let order = draws.iter()
+            .map(|d|(d.first_instance, d.layer, d.opaque))
+            .collect::<Vec<_>>();
+        assert_eq!(order, [
+            (1, 1, true),
+            (7, 1, true),
+            (4, 0, true),
+            (3, -1, false),
+            (0, 0, false),
+            (6, 0, false),
+            (5, 1, false),
+        ]);
+
+        // Higher layers are closer, whether or not they are opaque
+        let depths = layer_depths(&draws, 8);
+        assert_eq!(depths, [0.5, 0.25, 0.25, 0.75, 0.5, 0.25, 0.5, 0.25]);
+
+        // Opaque draws hide later draws on lower layers, and blended draws don't hide anything
+        let mode = |m: DepthStencilMode|(m.compare_op, m.depth_test, m.depth_write);
+        assert_eq!(mode(DEPTH_OPAQUE_2D), (vk::CompareOp::LESS_OR_EQUAL, true, true));
+        assert_eq!(mode(DEPTH_BLENDED_2D), (vk::CompareOp::LESS_OR_EQUAL, true, false));
+    }
+
+    #[test]
+    fn single_layer_depth() {
+        let depths = layer_depths(&[draw(0, 3, 7, false)], 3);
+        assert_eq!(depths, [0.5; 3]);
+    }
+
+    #[test]
+    fn clip_intersect() {
+        let outer = ClipRect::new(10, 20, 100, 50);
+        let check = |other: ClipRect, expected: ClipRect|{
+            assert_eq!(outer.intersect(&other), expected);
+            assert_eq!(other.intersect(&outer), expected);
+        };
+
+        // Nested
+        check(ClipRect::new(30, 30, 10, 10), ClipRect::new(30, 30, 10, 10));
+        check(outer, outer);
+        // Partly overlapping
+        check(ClipRect::new(0, 0, 40, 40), ClipRect::new(10, 20, 30, 20));
+        check(ClipRect::new(100, 60, 50, 50), ClipRect::new(100, 60, 10, 10));
+        check(ClipRect::new(-50, 30, 500, 5), ClipRect::new(10, 30, 100, 5));
+    }
+
+    #[test]
+    fn clip_intersect_empty() {
+        let outer = ClipRect::new(10, 20, 100, 50);
+        // Disjoint clips have no area, so nothing passes the scissor test
+        for other in [
+            ClipRect::new(200, 20, 10, 10),
+            ClipRect::new(10, 200, 10, 10),
+            ClipRect::new(-100, -100, 50, 50),
+            // Touching edges don't overlap
+            ClipRect::new(110, 20, 10, 10),
+            ClipRect::new(30, 30, 0, 10),
+        ] {
+            let clip = outer.intersect(&other);
+            assert!(clip.is_empty(), "{other:?} gave {clip:?}");
+            assert_eq!(clip.width * clip.height, 0, "{other:?} gave {clip:?}");
+            assert!(other.intersect(&outer).is_empty());
+        }
+
+        // An empty clip stays empty however it is intersected
+        let empty = outer.intersect(&ClipRect::new(200, 200, 10, 10));
+        assert!(empty.intersect(&outer).is_empty());
+        assert!(empty.intersect(&ClipRect::new(0, 0, 1000, 1000)).is_empty());
+    }
+
+    /// The float offset of each vertex input of the rounded rectangle shader, by name, and the
+    /// total size
+    fn rounded_rect_inputs()->(Vec<(String, usize)>, usize) {
+        let mut inputs = include_str!("../shaders/rounded_rect_vert.glsl").lines()
+            .filter_map(|line|{
+                let rest = line.trim().strip_prefix("layout(location = ")?;
+                let (location, rest) = rest.split_once(") in ")?;
+                let (ty, name) = rest.trim_end_matches(';').split_once(' ')?;
+                let size = match ty {
+                    "float"=>1,
+                    "vec2"=>2,
+                    "vec4"=>4,
+                    _=>panic!("Unexpected vertex input type `{ty}`"),
+                };
+                Some((location.parse::<usize>().unwrap(), name.to_string(), size))
+            })
+            .collect::<Vec<_>>();
+        inputs.sort_by_key(|(location, ..)|*location);
+
+        let mut offset = 0;
+        let offsets = inputs.into_iter()
+            .map(|(_, name, size)|{
+                offset += size;
+                (name, offset - size)
+            })
+            .collect();
+        return (offsets, offset);
+    }
+
+    #[test]
+    fn rounded_rect_layout() {
+        let (inputs, stride) = rounded_rect_inputs();
+        assert_eq!(stride, RoundedRect::VERTEX_SIZE);
+        let names = inputs.iter().map(|(name, _)|name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, [
+            "position", "local_in", "half_size_in", "radii_in", "fill_in", "border_color_in",
+            "border_width_in", "shadow_color_in", "shadow_offset_in", "shadow_blur_in", "shadow_spread_in",
+        ]);
+
+        let fill = Color(0.1, 0.2, 0.3, 0.4);
+        let border = Color(0.5, 0.6, 0.7, 0.8);
+        let shadow = Color(0.0, 0.0, 0.0, 0.5);
+        let rect = RoundedRect::new(Point2::new(10.0, 20.0), Vec2::new(100.0, 40.0), fill)
+            .with_radii([5.0, 50.0, -3.0, 15.0])
+            .with_border(2.0, border)
+            .with_shadow(BoxShadow {
+                color: shadow,
+                offset: Vec2::new(3.0, 4.0),
+                blur: 6.0,
+                spread: 2.0,
+            });
+        let vertices = rect.vertices();
+        assert_eq!(vertices.len(), stride * 4);
+
+        let input = |vertex: usize, name: &str, size: usize|{
+            let offset = inputs.iter().find(|(n, _)|n == name).unwrap().1;
+            &vertices[vertex * stride + offset..][..size]
+        };
+        // The shadow grows the quad by its spread and half of its blur, plus a pixel for
+        // anti-aliasing
+        let corners = [[7.0, 18.0], [119.0, 18.0], [7.0, 70.0], [119.0, 70.0]];
+        for (vertex, corner) in corners.into_iter().enumerate() {
+            assert_eq!(input(vertex, "position", 2), corner);
+            assert_eq!(input(vertex, "local_in", 2), [corner[0] - 60.0, corner[1] - 40.0]);
+            assert_eq!(input(vertex, "half_size_in", 2), [50.0, 20.0]);
+            // Radii are limited to half of the shortest side, and never negative
+            assert_eq!(input(vertex, "radii_in", 4), [5.0, 20.0, 0.0, 15.0]);
+            assert_eq!(input(vertex, "fill_in", 4), [0.1, 0.2, 0.3, 0.4]);
+            assert_eq!(input(vertex, "border_color_in", 4), [0.5, 0.6, 0.7, 0.8]);
+            assert_eq!(input(vertex, "border_width_in", 1), [2.0]);
+            assert_eq!(input(vertex, "shadow_color_in", 4), [0.0, 0.0, 0.0, 0.5]);
+            assert_eq!(input(vertex, "shadow_offset_in", 2), [3.0, 4.0]);
+            assert_eq!(input(vertex, "shadow_blur_in", 1), [6.0]);
+            assert_eq!(input(vertex, "shadow_spread_in", 1), [2.0]);
+        }
+    }
+
+    #[test]
+    fn rounded_rect_without_shadow() {
+        let rect = RoundedRect::new(Point2::new(10.0, 20.0), Vec2::new(30.0, 40.0), Color(1.0, 1.0, 1.0, 1.0));
+        assert_eq!(rect.bounds(), (Point2::new(9.0, 19.0), Point2::new(41.0, 61.0)));
+
+        // A shadow inside of the rectangle doesn't shrink the quad
+        let shadowed = rect.with_shadow(BoxShadow {
+            color: Color(0.0, 0.0, 0.0, 1.0),
+            offset: Vec2::zero(),
+            blur: 0.0,
+            spread: -5.0,
+        });
+        assert_eq!(shadowed.bounds(), rect.bounds());
+
+        // An invisible shadow is drawn when there isn't one
+        let (inputs, stride) = rounded_rect_inputs();
+        let vertices = rect.vertices();
+        for name in ["shadow_color_in", "shadow_offset_in", "shadow_blur_in", "shadow_spread_in"] {
+            let offset = inputs.iter().find(|(n, _)|n == name).unwrap().1;
+            assert!(vertices.chunks(stride).all(|v|v[offset] == 0.0), "{name}");
+        }
+        let radii = inputs.iter().find(|(n, _)|n == "radii_in").unwrap().1;
+        assert!(vertices.chunks(stride).all(|v|v[radii..radii + 4] == [0.0; 4]));
+    }
+
+    #[test]
+    fn rect_area() {
+        assert!(has_area(Vec2::new(1.0, 0.5)));
+        for size in [
+            Vec2::zero(),
+            Vec2::new(10.0, 0.0),
+            Vec2::new(-10.0, 10.0),
+            Vec2::new(10.0, -0.1),
+            Vec2::new(f32::NAN, 10.0),
+            Vec2::new(10.0, f32::NAN),
+        ] {
+            assert!(!has_area(size), "{size:?}");
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Turns lines and vector paths into triangles on the CPU, in the vertex format of
+//! [`Shape2D::ColorPolygon`].
+
+
+use anyhow::{
+    Result,
+    bail,
+};
+use crate::{
+    render::Shape2D,
+    math::*,
+    Color,
+};
+
+
+pub mod stroke;
+pub mod path;
+
+
+/// Triangles with a color for each vertex
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Point2>,
+    pub colors: Vec<Color>,
+    /// Three indices for each triangle
+    pub indices: Vec<u16>,
+}
+impl Mesh {
+    /// The most vertices a mesh can have with 16 bit indices
+    pub const MAX_VERTICES: usize = u16::MAX as usize + 1;
+
+    #[inline]
+    pub fn new()->Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn is_empty(&self)->bool {
+        self.indices.is_empty()
+    }
+
+    /// Add a vertex and return its index
+    pub fn vertex(&mut self, position: Point2, color: Color)->u16 {
+        self.vertices.push(position);
+        self.colors.push(color);
+        return (self.vertices.len() - 1) as u16;
+    }
+
+    #[inline]
+    pub fn triangle(&mut self, a: u16, b: u16, c: u16) {
+        self.indices.extend([a, b, c]);
+    }
+
+    /// Add all of the triangles in `other`. Errors without changing this mesh if there would be
+    /// too many vertices for 16 bit indices.
+    pub fn append(&mut self, other: &Mesh)->Result<()> {
+        let count = self.vertices.len() + other.vertices.len();
+        if count > Self::MAX_VERTICES {
+            bail!("Appending would give the mesh {count} vertices, but the most is {}", Self::MAX_VERTICES);
+        }
+
+        let offset = self.vertices.len() as u16;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.colors.extend_from_slice(&other.colors);
+        self.indices.extend(other.indices.iter().map(|i|i + offset));
+        return Ok(());
+    }
+
+    /// Errors if there are too many vertices for 16 bit indices
+    pub fn check(&self)->Result<()> {
+        if self.vertices.len() > Self::MAX_VERTICES {
+            bail!("Mesh has {} vertices, but the most is {}", self.vertices.len(), Self::MAX_VERTICES);
+        }
+
+        return Ok(());
+    }
+
+    pub fn into_shape(self)->Shape2D {
+        Shape2D::ColorPolygon {
+            colors: self.colors,
+            vertices: self.vertices,
+            indices: self.indices,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vertex_limit() {
+        let mut mesh = Mesh::new();
+        for _ in 0..Mesh::MAX_VERTICES {
+            mesh.vertex(Point2::zero(), Color::WHITE);
+        }
+        assert!(mesh.check().is_ok());
+
+        mesh.vertex(Point2::zero(), Color::WHITE);
+        assert!(mesh.check().is_err());
+    }
+
+    #[test]
+    fn append_offsets_indices() {
+        let mut a = Mesh::new();
+        let first = a.vertex(Point2::zero(), Color::WHITE);
+        a.triangle(first, first, first);
+        let mut b = Mesh::new();
+        let (x, y, z) = (
+            b.vertex(Point2::zero(), Color::WHITE),
+            b.vertex(Point2::zero(), Color::WHITE),
+            b.vertex(Point2::zero(), Color::WHITE),
+        );
+        b.triangle(x, y, z);
+
+        a.append(&b).unwrap();
+        assert_eq!(a.vertices.len(), 4);
+        assert_eq!(a.indices, vec![0, 0, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn append_limit() {
+        let mut a = Mesh::new();
+        for _ in 0..Mesh::MAX_VERTICES - 2 {
+            a.vertex(Point2::zero(), Color::WHITE);
+        }
+        let mut b = Mesh::new();
+        let (x, y, z) = (
+            b.vertex(Point2::zero(), Color::WHITE),
+            b.vertex(Point2::zero(), Color::WHITE),
+            b.vertex(Point2::zero(), Color::WHITE),
+        );
+        b.triangle(x, y, z);
+
+        assert!(a.append(&b).is_err());
+        assert_eq!(a.vertices.len(), Mesh::MAX_VERTICES - 2);
+        assert!(a.indices.is_empty());
+
+        b.vertices.pop();
+        b.colors.pop();
+        b.indices = vec![0, 1, 1];
+        a.append(&b).unwrap();
+        assert_eq!(a.indices, vec![65534, 65535, 65535]);
+    }
+}
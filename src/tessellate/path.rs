@@ -0,0 +1,544 @@
+//! Vector paths made of lines, curves and arcs that can be filled or stroked
+
+
+use anyhow::Result;
+use std::f32::consts::{
+    PI,
+    TAU,
+};
+use crate::{
+    math::*,
+    Color,
+};
+use super::{
+    stroke::{
+        LineStyle,
+        stroke,
+    },
+    Mesh,
+};
+
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathCommand {
+    /// Start a new contour
+    MoveTo(Point2),
+    LineTo(Point2),
+    /// A quadratic Bézier curve
+    QuadTo {
+        control: Point2,
+        to: Point2,
+    },
+    /// A cubic Bézier curve
+    CubicTo {
+        control1: Point2,
+        control2: Point2,
+        to: Point2,
+    },
+    /// A circular arc. Angles are in radians, and positive angles turn from +X towards +Y.
+    Arc {
+        center: Point2,
+        radius: f32,
+        start_angle: f32,
+        sweep: f32,
+    },
+    /// Join the contour back to its first point
+    Close,
+}
+
+/// Which areas of a path are inside when it is filled
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum FillRule {
+    /// Inside wherever the contours wind around a point a non-zero number of times. Holes have to
+    /// go the other way around from their outline.
+    #[default]
+    NonZero,
+    /// Inside wherever a point is surrounded by an odd number of contours. Holes can go either
+    /// way around.
+    EvenOdd,
+}
+impl FillRule {
+    #[inline]
+    fn inside(self, winding: i32)->bool {
+        match self {
+            Self::NonZero=>winding != 0,
+            Self::EvenOdd=>winding % 2 != 0,
+        }
+    }
+}
+
+/// A flattened part of a path
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contour {
+    pub points: Vec<Point2>,
+    pub closed: bool,
+}
+
+/// A list of drawing commands, built like
+/// `Path::new().move_to(a).line_to(b).quad_to(c, d).close()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub commands: Vec<PathCommand>,
+    /// The most curves and arcs may stray from their true shape when they are flattened into
+    /// lines. This is pixels with the default camera.
+    pub tolerance: f32,
+}
+impl Default for Path {
+    fn default()->Self {
+        Path {
+            commands: Vec::new(),
+            tolerance: 0.25,
+        }
+    }
+}
+impl Path {
+    #[inline]
+    pub fn new()->Self {
+        Self::default()
+    }
+
+    pub fn rect(top_left: Point2, size: Vec2)->Self {
+        Self::new()
+            .move_to(top_left)
+            .line_to(top_left + Vec2::new(size.x, 0.0))
+            .line_to(top_left + size)
+            .line_to(top_left + Vec2::new(0.0, size.y))
+            .close()
+    }
+
+    /// A rectangle with its corners rounded by `radius`. The radius is limited to half of the
+    /// shortest side.
+    pub fn rounded_rect(top_left: Point2, size: Vec2, radius: f32)->Self {
+        let radius = radius.min(size.x * 0.5).min(size.y * 0.5).max(0.0);
+        let inner_min = top_left + Vec2::broadcast(radius);
+        let inner_max = top_left + size - Vec2::broadcast(radius);
+
+        Self::new()
+            .arc(Point2::new(inner_max.x, inner_min.y), radius, -PI * 0.5, PI * 0.5)
+            .arc(inner_max, radius, 0.0, PI * 0.5)
+            .arc(Point2::new(inner_min.x, inner_max.y), radius, PI * 0.5, PI * 0.5)
+            .arc(inner_min, radius, PI, PI * 0.5)
+            .close()
+    }
+
+    pub fn circle(center: Point2, radius: f32)->Self {
+        Self::new()
+            .arc(center, radius, 0.0, TAU)
+            .close()
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f32)->Self {
+        self.tolerance = tolerance;
+        return self;
+    }
+
+    pub fn move_to(mut self, to: Point2)->Self {
+        self.commands.push(PathCommand::MoveTo(to));
+        return self;
+    }
+
+    pub fn line_to(mut self, to: Point2)->Self {
+        self.commands.push(PathCommand::LineTo(to));
+        return self;
+    }
+
+    pub fn quad_to(mut self, control: Point2, to: Point2)->Self {
+        self.commands.push(PathCommand::QuadTo {control, to});
+        return self;
+    }
+
+    pub fn cubic_to(mut self, control1: Point2, control2: Point2, to: Point2)->Self {
+        self.commands.push(PathCommand::CubicTo {control1, control2, to});
+        return self;
+    }
+
+    /// Add an arc. A line connects the current point to the start of the arc, or the arc starts a
+    /// new contour if there isn't one.
+    pub fn arc(mut self, center: Point2, radius: f32, start_angle: f32, sweep: f32)->Self {
+        self.commands.push(PathCommand::Arc {center, radius, start_angle, sweep});
+        return self;
+    }
+
+    pub fn close(mut self)->Self {
+        self.commands.push(PathCommand::Close);
+        return self;
+    }
+
+    /// Turn the curves and arcs into lines. Contours with less than two points are left out.
+    pub fn flatten(&self)->Vec<Contour> {
+        let tolerance = self.tolerance.max(0.001);
+        let mut contours = Vec::new();
+        let mut current: Option<Contour> = None;
+        // Where a contour starts if a line or curve comes without a `MoveTo` first
+        let mut cursor = Point2::zero();
+
+        fn finish(contours: &mut Vec<Contour>, contour: Option<Contour>) {
+            if let Some(contour) = contour.filter(|c|c.points.len() > 1) {
+                contours.push(contour);
+            }
+        }
+
+        for command in self.commands.iter().copied() {
+            if let PathCommand::MoveTo(to) = command {
+                finish(&mut contours, current.take());
+                current = Some(Contour {points: vec![to], closed: false});
+                cursor = to;
+                continue;
+            }
+
+            let start = match command {
+                PathCommand::Arc{center, radius, start_angle, ..}=>{
+                    let (sin, cos) = start_angle.sin_cos();
+                    center + Vec2::new(cos, sin) * radius
+                },
+                _=>cursor,
+            };
+            let contour = current.get_or_insert_with(||Contour {points: vec![start], closed: false});
+            let from = contour.points[contour.points.len() - 1];
+            match command {
+                PathCommand::MoveTo(..)=>unreachable!(),
+                PathCommand::LineTo(to)=>contour.points.push(to),
+                PathCommand::QuadTo{control, to}=>{
+                    let steps = curve_steps((from - control * 2.0 + to).mag() * 2.0, 8.0, tolerance);
+                    contour.points.extend((1..=steps).map(|i|{
+                        let t = i as f32 / steps as f32;
+                        let mt = 1.0 - t;
+                        from * (mt * mt) + control * (2.0 * mt * t) + to * (t * t)
+                    }));
+                },
+                PathCommand::CubicTo{control1, control2, to}=>{
+                    let bend = (from - control1 * 2.0 + control2).mag()
+                        .max((control1 - control2 * 2.0 + to).mag());
+                    let steps = curve_steps(bend * 6.0, 8.0, tolerance);
+                    contour.points.extend((1..=steps).map(|i|{
+                        let t = i as f32 / steps as f32;
+                        let mt = 1.0 - t;
+                        from * (mt * mt * mt) + control1 * (3.0 * mt * mt * t) + control2 * (3.0 * mt * t * t) + to * (t * t * t)
+                    }));
+                },
+                PathCommand::Arc{center, radius, start_angle, sweep}=>{
+                    let step = if radius > tolerance {
+                        2.0 * (1.0 - tolerance / radius).acos()
+                    } else {
+                        PI * 0.5
+                    };
+                    let steps = (sweep.abs() / step.max(0.01)).ceil().max(1.0) as u32;
+                    // Leave out the start of the arc if the contour is already there
+                    let first = if from == start {1} else {0};
+                    contour.points.extend((first..=steps).map(|i|{
+                        let (sin, cos) = (start_angle + sweep * i as f32 / steps as f32).sin_cos();
+                        center + Vec2::new(cos, sin) * radius
+                    }));
+                },
+                PathCommand::Close=>{
+                    contour.closed = true;
+                    cursor = contour.points[0];
+                    finish(&mut contours, current.take());
+                },
+            }
+        }
+        finish(&mut contours, current);
+
+        return contours;
+    }
+
+    /// Triangles covering the inside of the path. Every contour is treated as closed.
+    pub fn fill(&self, color: Color, rule: FillRule)->Result<Mesh> {
+        let mut edges = Vec::new();
+        for contour in self.flatten() {
+            let count = contour.points.len();
+            for i in 0..count {
+                let a = contour.points[i];
+                let b = contour.points[(i + 1) % count];
+                if a.y != b.y {
+                    edges.push(Edge::new(a, b));
+                }
+            }
+        }
+
+        let mut mesh = Mesh::new();
+        // The last vertex made for each edge and its height. Slabs that meet share their vertices
+        // on the edges they have in common.
+        let mut shared: Vec<Option<(f32, u16)>> = vec![None; edges.len()];
+        for (top, bottom) in slabs(&edges) {
+            // The edges crossing this slab, from left to right. No edges cross inside of a slab,
+            // so the order is the same at the top and bottom.
+            let mut crossing = edges.iter()
+                .enumerate()
+                .filter(|(_, e)|e.top.y <= top && e.bottom.y >= bottom)
+                .map(|(i, e)|(i, e.x_at(top), e.x_at(bottom), e.winding))
+                .collect::<Vec<_>>();
+            crossing.sort_by(|a, b|(a.1 + a.2).total_cmp(&(b.1 + b.2)));
+
+            // Whether the span right of each edge is inside
+            let mut winding = 0;
+            let inside = crossing.iter()
+                .map(|c|{
+                    winding += c.3;
+                    rule.inside(winding)
+                })
+                .collect::<Vec<_>>();
+
+            // The top and bottom vertices of the edges next to an inside span
+            let corners = crossing.iter()
+                .enumerate()
+                .map(|(j, &(edge, top_x, bottom_x, _))|{
+                    if !inside[j] && (j == 0 || !inside[j - 1]) {
+                        return None;
+                    }
+                    let top_index = match shared[edge] {
+                        Some((y, index)) if y == top=>index,
+                        _=>mesh.vertex(Point2::new(top_x, top), color),
+                    };
+                    let bottom_index = mesh.vertex(Point2::new(bottom_x, bottom), color);
+                    shared[edge] = Some((bottom, bottom_index));
+                    Some((top_index, bottom_index))
+                })
+                .collect::<Vec<_>>();
+
+            for (pair, inside) in corners.windows(2).zip(inside) {
+                if let (true, Some((a, d)), Some((b, c))) = (inside, pair[0], pair[1]) {
+                    mesh.triangle(a, b, c);
+                    mesh.triangle(a, c, d);
+                }
+            }
+        }
+
+        mesh.check()?;
+        return Ok(mesh);
+    }
+
+    /// A line along each contour of the path
+    pub fn stroke(&self, color: Color, style: &LineStyle)->Result<Mesh> {
+        let mut mesh = Mesh::new();
+        for contour in self.flatten() {
+            let colors = vec![color; contour.points.len()];
+            mesh.append(&stroke(&contour.points, &colors, style, contour.closed)?)?;
+        }
+
+        mesh.check()?;
+        return Ok(mesh);
+    }
+}
+
+/// How many lines a curve needs to stay within `tolerance`. `bend` is the size of the curve's
+/// second difference, and the error of `n` lines is about `bend / (scale * n²)`.
+fn curve_steps(bend: f32, scale: f32, tolerance: f32)->u32 {
+    return (bend / (scale * tolerance)).sqrt().ceil().clamp(1.0, 1000.0) as u32;
+}
+
+/// A non-horizontal line of a contour, ordered top to bottom
+struct Edge {
+    top: Point2,
+    bottom: Point2,
+    /// `1` if the contour goes down along this edge, `-1` if it goes up
+    winding: i32,
+}
+impl Edge {
+    fn new(a: Point2, b: Point2)->Self {
+        if a.y < b.y {
+            Edge {top: a, bottom: b, winding: 1}
+        } else {
+            Edge {top: b, bottom: a, winding: -1}
+        }
+    }
+
+    #[inline]
+    fn x_at(&self, y: f32)->f32 {
+        let t = (y - self.top.y) / (self.bottom.y - self.top.y);
+        return self.top.x + (self.bottom.x - self.top.x) * t;
+    }
+
+    /// The height at which both edges cross, if they do
+    fn crossing(&self, other: &Self)->Option<f32> {
+        let r = self.bottom - self.top;
+        let s = other.bottom - other.top;
+        let denominator = r.x * s.y - r.y * s.x;
+        if denominator.abs() < 1e-9 {
+            return None;
+        }
+
+        let q = other.top - self.top;
+        let t = (q.x * s.y - q.y * s.x) / denominator;
+        let u = (q.x * r.y - q.y * r.x) / denominator;
+        if t <= 0.0 || t >= 1.0 || u <= 0.0 || u >= 1.0 {
+            return None;
+        }
+        return Some(self.top.y + r.y * t);
+    }
+}
+
+/// Split the height of the edges into slabs at every end point and crossing, so no edges cross
+/// inside of a slab
+fn slabs(edges: &[Edge])->Vec<(f32, f32)> {
+    let mut heights = edges.iter()
+        .flat_map(|e|[e.top.y, e.bottom.y])
+        .collect::<Vec<_>>();
+
+    // Sorted by their tops, only the edges after `a` that start above its bottom can cross it
+    let mut sorted = edges.iter().collect::<Vec<_>>();
+    sorted.sort_by(|a, b|a.top.y.total_cmp(&b.top.y));
+    for (i, a) in sorted.iter().enumerate() {
+        for b in sorted[i + 1..].iter().take_while(|b|b.top.y < a.bottom.y) {
+            heights.extend(a.crossing(b));
+        }
+    }
+    heights.sort_by(f32::total_cmp);
+    heights.dedup();
+
+    return heights.windows(2)
+        .map(|pair|(pair[0], pair[1]))
+        .filter(|(top, bottom)|bottom - top > 1e-6)
+        .collect();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The area covered by the triangles of a mesh
+    fn area(mesh: &Mesh)->f32 {
+        return mesh.indices.chunks(3)
+            .map(|t|{
+                let [a, b, c] = [0, 1, 2].map(|i|mesh.vertices[t[i] as usize]);
+                let (ab, ac) = (b - a, c - a);
+                (ab.x * ac.y - ab.y * ac.x).abs() * 0.5
+            })
+            .sum();
+    }
+
+    /// A 100×100 square with a 50×50 hole in the middle
+    fn square_with_hole(reverse_hole: bool)->Path {
+        let path = Path::rect(Point2::zero(), Vec2::broadcast(100.0));
+        let hole = [(25.0, 25.0), (75.0, 25.0), (75.0, 75.0), (25.0, 75.0)].map(|(x, y)|Point2::new(x, y));
+        let mut hole = hole.to_vec();
+        if reverse_hole {
+            hole.reverse();
+        }
+
+        let mut path = path.move_to(hole[0]);
+        for point in &hole[1..] {
+            path = path.line_to(*point);
+        }
+        return path.close();
+    }
+
+    /// The most a flattened curve strays from `curve` between its points
+    fn max_error(points: &[Point2], curve: impl Fn(f32)->Point2)->f32 {
+        let steps = points.len() - 1;
+        return (0..steps)
+            .map(|i|{
+                let middle = curve((i as f32 + 0.5) / steps as f32);
+                let (a, b) = (points[i], points[i + 1]);
+                let along = (b - a).normalized();
+                let offset = middle - a;
+                (offset - along * offset.dot(along)).mag()
+            })
+            .fold(0.0, f32::max);
+    }
+
+    #[test]
+    fn flatten_lines() {
+        let contours = Path::rect(Point2::zero(), Vec2::broadcast(10.0)).flatten();
+        assert_eq!(contours.len(), 1);
+        assert_eq!(contours[0].points.len(), 4);
+        assert!(contours[0].closed);
+
+        // Single points are left out
+        let contours = Path::new().move_to(Point2::zero()).move_to(Point2::one()).line_to(Point2::zero()).flatten();
+        assert_eq!(contours, vec![Contour {points: vec![Point2::one(), Point2::zero()], closed: false}]);
+    }
+
+    #[test]
+    fn flatten_circle() {
+        let radius = 100.0;
+        let mut last = 0;
+        for tolerance in [2.0, 1.0, 0.25, 0.05] {
+            let points = Path::circle(Point2::zero(), radius).with_tolerance(tolerance).flatten().remove(0).points;
+            let step = 2.0 * (1.0 - tolerance / radius).acos();
+            assert_eq!(points.len(), (TAU / step).ceil() as usize + 1, "{tolerance}");
+            assert!(points.len() > last);
+            last = points.len();
+
+            let error = max_error(&points, |t|{
+                let (sin, cos) = (t * TAU).sin_cos();
+                Point2::new(cos, sin) * radius
+            });
+            assert!(error <= tolerance * 1.01, "{tolerance}: {error}");
+        }
+    }
+
+    #[test]
+    fn flatten_curves() {
+        let (from, control1, control2, to) = (
+            Point2::new(0.0, 0.0),
+            Point2::new(50.0, 200.0),
+            Point2::new(150.0, -100.0),
+            Point2::new(200.0, 0.0),
+        );
+        let mut last = (0, 0);
+        for tolerance in [2.0, 1.0, 0.25, 0.05] {
+            let quad = Path::new().move_to(from).quad_to(control1, to).with_tolerance(tolerance).flatten().remove(0).points;
+            let error = max_error(&quad, |t|{
+                let mt = 1.0 - t;
+                from * (mt * mt) + control1 * (2.0 * mt * t) + to * (t * t)
+            });
+            assert!(error <= tolerance, "quad {tolerance}: {error}");
+
+            let cubic = Path::new().move_to(from).cubic_to(control1, control2, to).with_tolerance(tolerance).flatten().remove(0).points;
+            let error = max_error(&cubic, |t|{
+                let mt = 1.0 - t;
+                from * (mt * mt * mt) + control1 * (3.0 * mt * mt * t) + control2 * (3.0 * mt * t * t) + to * (t * t * t)
+            });
+            assert!(error <= tolerance, "cubic {tolerance}: {error}");
+
+            assert!(quad.len() > last.0 && cubic.len() > last.1);
+            last = (quad.len(), cubic.len());
+        }
+
+        // A straight curve is one line
+        let straight = Path::new().move_to(from).quad_to(Point2::new(100.0, 0.0), to).flatten().remove(0).points;
+        assert_eq!(straight.len(), 2);
+    }
+
+    #[test]
+    fn fill_rect() {
+        let mesh = Path::rect(Point2::zero(), Vec2::new(100.0, 50.0)).fill(Color::WHITE, FillRule::NonZero).unwrap();
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices.len(), 6);
+        assert!((area(&mesh) - 5000.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn fill_hole() {
+        let fill = |reverse_hole, rule|square_with_hole(reverse_hole).fill(Color::WHITE, rule).unwrap();
+
+        // A hole going the same way around adds to the winding
+        assert!((area(&fill(false, FillRule::NonZero)) - 10000.0).abs() < 1e-2);
+        assert!((area(&fill(false, FillRule::EvenOdd)) - 7500.0).abs() < 1e-2);
+        assert!((area(&fill(true, FillRule::NonZero)) - 7500.0).abs() < 1e-2);
+        assert!((area(&fill(true, FillRule::EvenOdd)) - 7500.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn fill_shares_vertices() {
+        // Three slabs with two edges each, where each edge has a vertex at every slab boundary
+        let mesh = square_with_hole(true).fill(Color::WHITE, FillRule::NonZero).unwrap();
+        assert_eq!(mesh.vertices.len(), 12);
+        assert_eq!(mesh.indices.len(), 8 * 3);
+    }
+
+    #[test]
+    fn fill_crossing() {
+        // A bow tie crosses itself in the middle
+        let path = Path::new()
+            .move_to(Point2::new(0.0, 0.0))
+            .line_to(Point2::new(100.0, 100.0))
+            .line_to(Point2::new(100.0, 0.0))
+            .line_to(Point2::new(0.0, 100.0))
+            .close();
+        let mesh = path.fill(Color::WHITE, FillRule::NonZero).unwrap();
+        assert!((area(&mesh) - 5000.0).abs() < 1e-2);
+    }
+}
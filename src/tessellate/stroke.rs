@@ -0,0 +1,668 @@
+//! Thick lines with joins, caps, dashes and anti-aliased edges
+
+
+use anyhow::{
+    Result,
+    bail,
+};
+use std::f32::consts::PI;
+use crate::{
+    math::*,
+    Color,
+};
+use super::Mesh;
+
+
+/// Half the width of the fade at the edges of anti-aliased lines, in pixels. See
+/// [`LineStyle::pixels_per_unit`].
+const FRINGE: f32 = 0.5;
+/// The most a round join or cap may stray from a true circle, in pixels
+const ROUND_TOLERANCE: f32 = 0.25;
+
+
+/// How two segments of a line meet
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum LineJoin {
+    /// Extend the edges until they meet. Falls back to [`LineJoin::Bevel`] past
+    /// [`LineStyle::miter_limit`].
+    #[default]
+    Miter,
+    Round,
+    /// Cut the corner off
+    Bevel,
+}
+
+/// How the ends of a line (and each dash) look
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum LineCap {
+    /// Stop at the end point
+    #[default]
+    Butt,
+    Round,
+    /// Extend past the end point by half the width
+    Square,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineStyle {
+    /// Width in the shape's units. This is pixels with the default camera.
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// The longest a miter join can be, as a multiple of the width
+    pub miter_limit: f32,
+    /// Alternating lengths of dashes and gaps, starting with a dash. An odd number of lengths is
+    /// repeated once to make it even. Empty draws a solid line.
+    pub dashes: Vec<f32>,
+    /// How far into the dash pattern the line starts
+    pub dash_offset: f32,
+    /// Fade the edges out over a pixel instead of cutting them off
+    pub anti_alias: bool,
+    /// How many pixels one unit of the shape covers on screen, from the camera zoom and the
+    /// shape's transform. The anti-aliased fade and the smoothness of round joins and caps are
+    /// measured in pixels.
+    pub pixels_per_unit: f32,
+}
+impl Default for LineStyle {
+    fn default()->Self {
+        LineStyle {
+            width: 1.0,
+            join: LineJoin::default(),
+            cap: LineCap::default(),
+            miter_limit: 4.0,
+            dashes: Vec::new(),
+            dash_offset: 0.0,
+            anti_alias: true,
+            pixels_per_unit: 1.0,
+        }
+    }
+}
+impl LineStyle {
+    pub fn new(width: f32)->Self {
+        LineStyle {
+            width,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_join(mut self, join: LineJoin)->Self {
+        self.join = join;
+        return self;
+    }
+
+    pub fn with_cap(mut self, cap: LineCap)->Self {
+        self.cap = cap;
+        return self;
+    }
+
+    pub fn with_miter_limit(mut self, miter_limit: f32)->Self {
+        self.miter_limit = miter_limit;
+        return self;
+    }
+
+    pub fn with_dashes(mut self, dashes: Vec<f32>, offset: f32)->Self {
+        self.dashes = dashes;
+        self.dash_offset = offset;
+        return self;
+    }
+
+    pub fn with_anti_alias(mut self, anti_alias: bool)->Self {
+        self.anti_alias = anti_alias;
+        return self;
+    }
+
+    pub fn with_pixels_per_unit(mut self, pixels_per_unit: f32)->Self {
+        self.pixels_per_unit = pixels_per_unit;
+        return self;
+    }
+
+    /// The dash pattern with an even number of lengths, or `None` for a solid line. Errors if a
+    /// length is negative or not a number, or if they add up to zero.
+    fn dash_pattern(&self)->Result<Option<Vec<f32>>> {
+        if self.dashes.is_empty() {
+            return Ok(None);
+        }
+        if let Some(bad) = self.dashes.iter().find(|d|**d < 0.0 || !d.is_finite()) {
+            bail!("Dash lengths must be positive numbers, but got {bad}");
+        }
+        if self.dashes.iter().sum::<f32>() <= 0.0 || !self.dash_offset.is_finite() {
+            bail!("Dash pattern {:?} with offset {} has no length", self.dashes, self.dash_offset);
+        }
+
+        let mut pattern = self.dashes.clone();
+        if pattern.len() % 2 == 1 {
+            pattern.extend_from_slice(&self.dashes);
+        }
+        return Ok(Some(pattern));
+    }
+}
+
+/// Tessellate a line through `points` into triangles. Each point has a color in `colors` which is
+/// blended along the segments. A `closed` line also joins the last point back to the first one.
+/// The mesh is empty if the width is zero or there is nothing to draw.
+pub fn stroke(points: &[Point2], colors: &[Color], style: &LineStyle, closed: bool)->Result<Mesh> {
+    if colors.len() != points.len() {
+        bail!("Got {} colors for {} points", colors.len(), points.len());
+    }
+    if !style.width.is_finite() {
+        bail!("Line width must be a number, but got {}", style.width);
+    }
+    if style.pixels_per_unit <= 0.0 || !style.pixels_per_unit.is_finite() {
+        bail!("Pixels per unit must be a positive number, but got {}", style.pixels_per_unit);
+    }
+    let pattern = style.dash_pattern()?;
+
+    let mut mesh = Mesh::new();
+    if style.width <= 0.0 {
+        return Ok(mesh);
+    }
+
+    let mut line = dedup(points.iter().copied().zip(colors.iter().copied()));
+    if closed && line.len() > 2 && same_point(line[0].0, line[line.len() - 1].0) {
+        line.pop();
+    }
+
+    let mut stroker = Stroker::new(style, &mut mesh);
+    match pattern {
+        Some(pattern)=>{
+            if closed && !line.is_empty() {
+                line.push(line[0]);
+            }
+            for dash in dashes(&line, &pattern, style.dash_offset)? {
+                stroker.polyline(&dedup(dash.into_iter()), false);
+            }
+        },
+        None=>stroker.polyline(&line, closed && line.len() > 2),
+    }
+
+    mesh.check()?;
+    return Ok(mesh);
+}
+
+#[inline]
+fn same_point(a: Point2, b: Point2)->bool {
+    (a - b).mag_sq() <= 1e-8
+}
+
+/// Remove repeated points, since they have no direction to join or cap with
+fn dedup(line: impl Iterator<Item = (Point2, Color)>)->Vec<(Point2, Color)> {
+    let mut out: Vec<(Point2, Color)> = Vec::new();
+    for (point, color) in line {
+        if out.last().is_none_or(|(last, _)|!same_point(point, *last)) {
+            out.push((point, color));
+        }
+    }
+
+    return out;
+}
+
+/// Cut a line into the dashes of `pattern`. Zero length dashes are single points. Errors if there
+/// would be more dashes than fit in a mesh.
+fn dashes(line: &[(Point2, Color)], pattern: &[f32], offset: f32)->Result<Vec<Vec<(Point2, Color)>>> {
+    let mut out = Vec::new();
+    if line.is_empty() {
+        return Ok(out);
+    }
+
+    // Distances are measured from the start of the pattern, in 64 bits so tiny dashes on long
+    // lines still land in the right place
+    let total = pattern.iter().map(|l|*l as f64).sum::<f64>();
+    let ends = pattern.iter()
+        .scan(0.0, |sum, l|{
+            *sum += *l as f64;
+            Some(*sum)
+        })
+        .collect::<Vec<f64>>();
+    let offset = (offset as f64).rem_euclid(total);
+    let length = line.windows(2)
+        .map(|s|(s[1].0 - s[0].0).mag() as f64)
+        .sum::<f64>();
+
+    let cuts = ((length + offset) / total).ceil() * pattern.len() as f64;
+    if cuts > Mesh::MAX_VERTICES as f64 {
+        bail!("Dash pattern {pattern:?} cuts a line of length {length} into too many dashes");
+    }
+
+    // Where the `n`th length of the pattern ends along the line
+    let cut = |n: usize|(n / pattern.len()) as f64 * total + ends[n % pattern.len()] - offset;
+    let mut n = ends.iter().position(|end|*end >= offset).unwrap_or(0);
+
+    let mut dash = Vec::new();
+    if n % 2 == 0 {
+        dash.push(line[0]);
+    }
+    let mut start = 0.0;
+    for segment in line.windows(2) {
+        let (a, a_color) = segment[0];
+        let (b, b_color) = segment[1];
+        let length = (b - a).mag() as f64;
+        let end = start + length;
+
+        while cut(n) < end {
+            let t = ((cut(n) - start) / length) as f32;
+            dash.push((a + (b - a) * t, a_color.lerp(&b_color, t)));
+            if n % 2 == 0 {
+                out.push(std::mem::take(&mut dash));
+            }
+            n += 1;
+        }
+
+        if n % 2 == 0 {
+            dash.push((b, b_color));
+        }
+        start = end;
+    }
+    if !dash.is_empty() {
+        out.push(dash);
+    }
+
+    return Ok(out);
+}
+
+#[inline]
+fn perp(v: Vec2)->Vec2 {
+    Vec2::new(-v.y, v.x)
+}
+
+#[inline]
+fn cross(a: Vec2, b: Vec2)->f32 {
+    a.x * b.y - a.y * b.x
+}
+
+#[inline]
+fn rotate(v: Vec2, angle: f32)->Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+/// Writes the triangles of one style of line into a mesh
+struct Stroker<'a> {
+    mesh: &'a mut Mesh,
+    join: LineJoin,
+    cap: LineCap,
+    miter_limit: f32,
+    half_width: f32,
+    /// [`ROUND_TOLERANCE`] in the shape's units
+    round_tolerance: f32,
+    /// Distance from the center out to each ring of vertices and their alpha, from the inside out.
+    /// With anti-aliasing there is a solid core and a fringe that fades out.
+    rings: Vec<(f32, f32)>,
+}
+impl<'a> Stroker<'a> {
+    fn new(style: &LineStyle, mesh: &'a mut Mesh)->Self {
+        let half_width = style.width * 0.5;
+        let fringe = FRINGE / style.pixels_per_unit;
+        let rings = if style.anti_alias {
+            // Lines thinner than a pixel fade out instead of getting thinner
+            let coverage = (style.width * style.pixels_per_unit).min(1.0);
+            vec![((half_width - fringe).max(0.0), coverage), (half_width + fringe, 0.0)]
+        } else {
+            vec![(half_width, 1.0)]
+        };
+
+        Stroker {
+            mesh,
+            join: style.join,
+            cap: style.cap,
+            miter_limit: style.miter_limit,
+            half_width,
+            round_tolerance: ROUND_TOLERANCE / style.pixels_per_unit,
+            rings,
+        }
+    }
+
+    #[inline]
+    fn outer(&self)->f32 {
+        self.rings[self.rings.len() - 1].0
+    }
+
+    fn polyline(&mut self, line: &[(Point2, Color)], closed: bool) {
+        let count = line.len();
+        if count == 0 {
+            return;
+        }
+        if count == 1 {
+            // Zero length dashes are dots with round and square caps
+            let (point, color) = line[0];
+            if self.cap != LineCap::Butt {
+                let start = self.cap(point, color, Vec2::unit_x(), false);
+                let end = self.cap(point, color, Vec2::unit_x(), true);
+                self.strip(&start, &end);
+            }
+            return;
+        }
+
+        let segments = if closed {count} else {count - 1};
+        let directions = (0..segments)
+            .map(|i|{
+                let d = line[(i + 1) % count].0 - line[i].0;
+                (d.normalized(), d.mag())
+            })
+            .collect::<Vec<_>>();
+
+        // The section that ends the segment before each point, and the one that starts the
+        // segment after it
+        let mut sections = Vec::with_capacity(count);
+        for (i, (point, color)) in line.iter().copied().enumerate() {
+            let before = (closed || i > 0).then(||directions[(i + segments - 1) % segments]);
+            let after = (closed || i < count - 1).then(||directions[i % segments]);
+            let section = match (before, after) {
+                (Some(before), Some(after))=>self.join(point, color, before, after),
+                (None, Some((after, _)))=>{
+                    let start = self.cap(point, color, after, false);
+                    (start.clone(), start)
+                },
+                (Some((before, _)), None)=>{
+                    let end = self.cap(point, color, before, true);
+                    (end.clone(), end)
+                },
+                (None, None)=>unreachable!(),
+            };
+            sections.push(section);
+        }
+
+        for i in 0..segments {
+            let start = sections[i].1.clone();
+            self.strip(&start, &sections[(i + 1) % count].0);
+        }
+    }
+
+    /// A row of vertices across the line at `center`. The edges are at `center ± normal` times
+    /// the ring distances.
+    fn section(&mut self, center: Point2, normal: Vec2, color: Color)->Vec<u16> {
+        let mut section = Vec::with_capacity(self.rings.len() * 2);
+        for (distance, alpha) in self.rings.iter().rev() {
+            section.push(self.mesh.vertex(center + normal * *distance, faded(color, *alpha)));
+        }
+        for (distance, alpha) in self.rings.iter() {
+            section.push(self.mesh.vertex(center - normal * *distance, faded(color, *alpha)));
+        }
+
+        return section;
+    }
+
+    /// Fill the space between two sections
+    fn strip(&mut self, a: &[u16], b: &[u16]) {
+        for i in 0..a.len() - 1 {
+            self.mesh.triangle(a[i], b[i], a[i + 1]);
+            self.mesh.triangle(a[i + 1], b[i], b[i + 1]);
+        }
+    }
+
+    /// A circle slice around `center` that starts in direction `from` and turns by `sweep` radians
+    /// in `steps` straight edges
+    fn fan(&mut self, center: Point2, color: Color, from: Vec2, sweep: f32, steps: u32) {
+        let center_index = self.mesh.vertex(center, faded(color, self.rings[0].1));
+        let mut last: Option<Vec<u16>> = None;
+        for step in 0..=steps {
+            let direction = rotate(from, sweep * step as f32 / steps as f32);
+            let ring = self.rings.iter()
+                .map(|(distance, alpha)|self.mesh.vertex(center + direction * *distance, faded(color, *alpha)))
+                .collect::<Vec<_>>();
+
+            if let Some(last) = last {
+                self.mesh.triangle(center_index, last[0], ring[0]);
+                for i in 0..ring.len() - 1 {
+                    self.mesh.triangle(last[i], last[i + 1], ring[i]);
+                    self.mesh.triangle(last[i + 1], ring[i + 1], ring[i]);
+                }
+            }
+            last = Some(ring);
+        }
+    }
+
+    /// How many edges a round join or cap turning by `sweep` needs to look smooth
+    fn round_steps(&self, sweep: f32)->u32 {
+        let radius = self.outer();
+        let step = if radius > self.round_tolerance {
+            2.0 * (1.0 - self.round_tolerance / radius).acos()
+        } else {
+            PI * 0.5
+        };
+
+        return (sweep.abs() / step.max(0.01)).ceil().max(1.0) as u32;
+    }
+
+    /// The sections on both sides of the corner between two segments, with the corner filled in
+    /// between them
+    fn join(&mut self, point: Point2, color: Color, before: (Vec2, f32), after: (Vec2, f32))->(Vec<u16>, Vec<u16>) {
+        let (before, before_length) = before;
+        let (after, after_length) = after;
+        let before_normal = perp(before);
+        let after_normal = perp(after);
+        let turn = cross(before, after);
+
+        if turn.abs() < 1e-6 && before.dot(after) > 0.0 {
+            let section = self.section(point, before_normal, color);
+            return (section.clone(), section);
+        }
+
+        let miter = before_normal + after_normal;
+        if self.join == LineJoin::Miter && miter.mag_sq() > 1e-12 {
+            let miter = miter.normalized();
+            let scale = 1.0 / miter.dot(before_normal);
+            // The inner corner can't reach past the neighbouring points
+            let fits = self.outer() * scale <= before_length.min(after_length);
+            if scale <= self.miter_limit && fits {
+                let section = self.section(point, miter * scale, color);
+                return (section.clone(), section);
+            }
+        }
+
+        let end = self.section(point, before_normal, color);
+        let start = self.section(point, after_normal, color);
+
+        // The corner is filled on the outside of the turn
+        let side = if turn > 0.0 {-1.0} else {1.0};
+        let from = before_normal * side;
+        let to = after_normal * side;
+        let sweep = cross(from, to).atan2(from.dot(to));
+        let steps = match self.join {
+            LineJoin::Round=>self.round_steps(sweep),
+            LineJoin::Miter|LineJoin::Bevel=>1,
+        };
+        self.fan(point, color, from, sweep, steps);
+
+        return (end, start);
+    }
+
+    /// The section at an end of the line, with the cap added past it. `direction` is the direction
+    /// of the segment, and `end` says whether this is the last point or the first.
+    fn cap(&mut self, point: Point2, color: Color, direction: Vec2, end: bool)->Vec<u16> {
+        let normal = perp(direction);
+        let outward = if end {direction} else {-direction};
+
+        match self.cap {
+            LineCap::Round=>{
+                let section = self.section(point, normal, color);
+                let sweep = PI * cross(normal, outward).signum();
+                let steps = self.round_steps(sweep);
+                self.fan(point, color, normal, sweep, steps);
+                return section;
+            },
+            LineCap::Butt|LineCap::Square=>{
+                let extend = if self.cap == LineCap::Square {self.half_width} else {0.0};
+                // The fade is centered on the end, like it is on the sides
+                let fringe = self.outer() - self.half_width;
+                let section = self.section(point + outward * (extend - fringe), normal, color);
+                if fringe > 0.0 {
+                    let faded_out = self.section(point + outward * (extend + fringe), normal, faded(color, 0.0));
+                    self.strip(&section, &faded_out);
+                }
+                return section;
+            },
+        }
+    }
+}
+
+#[inline]
+fn faded(color: Color, alpha: f32)->Color {
+    Color {
+        a: color.a * alpha,
+        ..color
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(points: &[(f32, f32)])->(Vec<Point2>, Vec<Color>) {
+        let points = points.iter()
+            .map(|(x, y)|Point2::new(*x, *y))
+            .collect::<Vec<_>>();
+        let colors = vec![Color::WHITE; points.len()];
+        return (points, colors);
+    }
+
+    /// Vertex and index counts of a stroke without anti-aliasing
+    fn counts(points: &[(f32, f32)], style: LineStyle)->(usize, usize) {
+        let (points, colors) = line(points);
+        let mesh = stroke(&points, &colors, &style.with_anti_alias(false), false).unwrap();
+        return (mesh.vertices.len(), mesh.indices.len());
+    }
+
+    /// The x range of each dash
+    fn dash_spans(pattern: &[f32], offset: f32)->Vec<(f32, f32)> {
+        let (points, colors) = line(&[(0.0, 0.0), (100.0, 0.0)]);
+        let line = points.into_iter().zip(colors).collect::<Vec<_>>();
+        return dashes(&line, pattern, offset).unwrap().into_iter()
+            .map(|dash|(dash[0].0.x, dash[dash.len() - 1].0.x))
+            .collect();
+    }
+
+    fn assert_spans(actual: &[(f32, f32)], expected: &[(f32, f32)]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a.0 - e.0).abs() < 1e-3 && (a.1 - e.1).abs() < 1e-3, "expected {expected:?}, got {actual:?}");
+        }
+    }
+
+    #[test]
+    fn cap_counts() {
+        let straight = [(0.0, 0.0), (100.0, 0.0)];
+        // Two sections of two vertices with a quad between them
+        assert_eq!(counts(&straight, LineStyle::new(10.0).with_cap(LineCap::Butt)), (4, 6));
+        assert_eq!(counts(&straight, LineStyle::new(10.0).with_cap(LineCap::Square)), (4, 6));
+        // Each cap adds a fan of 5 triangles: a center and 6 points around a half circle
+        assert_eq!(counts(&straight, LineStyle::new(10.0).with_cap(LineCap::Round)), (18, 36));
+    }
+
+    #[test]
+    fn join_counts() {
+        let corner = [(0.0, 0.0), (100.0, 0.0), (100.0, 100.0)];
+        // The miter shares one section between both segments
+        assert_eq!(counts(&corner, LineStyle::new(10.0).with_join(LineJoin::Miter)), (6, 12));
+        // A bevel has a section on each side of the corner and one triangle between them
+        assert_eq!(counts(&corner, LineStyle::new(10.0).with_join(LineJoin::Bevel)), (11, 15));
+        // A round join fans 3 triangles over the quarter turn
+        assert_eq!(counts(&corner, LineStyle::new(10.0).with_join(LineJoin::Round)), (13, 21));
+        // Past the miter limit it is a bevel
+        let sharp = [(0.0, 0.0), (100.0, 0.0), (0.0, 10.0)];
+        assert_eq!(counts(&sharp, LineStyle::new(10.0).with_join(LineJoin::Miter)), (11, 15));
+    }
+
+    #[test]
+    fn anti_alias_counts() {
+        let (points, colors) = line(&[(0.0, 0.0), (100.0, 0.0)]);
+        let mesh = stroke(&points, &colors, &LineStyle::new(10.0), false).unwrap();
+        // Sections have a core and a fringe on both sides, and each end has a strip fading out
+        assert_eq!(mesh.vertices.len(), 16);
+        assert_eq!(mesh.indices.len(), 54);
+    }
+
+    #[test]
+    fn fringe_in_pixels() {
+        let (points, colors) = line(&[(0.0, 0.0), (100.0, 0.0)]);
+        for pixels_per_unit in [1.0, 4.0, 0.5] {
+            let style = LineStyle::new(10.0).with_pixels_per_unit(pixels_per_unit);
+            let mesh = stroke(&points, &colors, &style, false).unwrap();
+            let top = mesh.vertices.iter().map(|v|v.y).fold(f32::MIN, f32::max);
+            assert!((top - (5.0 + 0.5 / pixels_per_unit)).abs() < 1e-4, "{pixels_per_unit}: {top}");
+        }
+    }
+
+    #[test]
+    fn empty_strokes() {
+        let (points, colors) = line(&[(0.0, 0.0), (100.0, 0.0)]);
+        assert!(stroke(&points, &colors, &LineStyle::new(0.0), false).unwrap().is_empty());
+
+        let (points, colors) = line(&[(5.0, 5.0), (5.0, 5.0)]);
+        assert!(stroke(&points, &colors, &LineStyle::new(4.0), false).unwrap().is_empty());
+        // A single point is a dot with round caps
+        let style = LineStyle::new(4.0).with_cap(LineCap::Round);
+        assert!(!stroke(&points, &colors, &style, false).unwrap().is_empty());
+
+        assert!(stroke(&points, &colors[..1], &LineStyle::new(4.0), false).is_err());
+    }
+
+    #[test]
+    fn dash_splitting() {
+        let expected = [(0.0, 10.0), (15.0, 25.0), (30.0, 40.0), (45.0, 55.0), (60.0, 70.0), (75.0, 85.0), (90.0, 100.0)];
+        assert_spans(&dash_spans(&[10.0, 5.0], 0.0), &expected);
+
+        // Starting 12 into the pattern is 2 into the gap
+        let expected = [(3.0, 13.0), (18.0, 28.0), (33.0, 43.0), (48.0, 58.0), (63.0, 73.0), (78.0, 88.0), (93.0, 100.0)];
+        assert_spans(&dash_spans(&[10.0, 5.0], 12.0), &expected);
+        // Offsets wrap around the pattern in both directions
+        assert_spans(&dash_spans(&[10.0, 5.0], 27.0), &expected);
+        assert_spans(&dash_spans(&[10.0, 5.0], -3.0), &expected);
+
+        // Starting inside a dash shortens the first one
+        assert_spans(&dash_spans(&[10.0, 5.0], 4.0)[..2], &[(0.0, 6.0), (11.0, 21.0)]);
+    }
+
+    #[test]
+    fn odd_dash_pattern() {
+        let style = LineStyle::new(1.0).with_dashes(vec![10.0], 0.0);
+        let pattern = style.dash_pattern().unwrap().unwrap();
+        assert_eq!(pattern, vec![10.0, 10.0]);
+        assert_spans(&dash_spans(&pattern, 0.0), &[(0.0, 10.0), (20.0, 30.0), (40.0, 50.0), (60.0, 70.0), (80.0, 90.0)]);
+
+        // Odd patterns repeat twice, so each length is a dash then a gap
+        let style = LineStyle::new(1.0).with_dashes(vec![10.0, 5.0, 20.0], 0.0);
+        let pattern = style.dash_pattern().unwrap().unwrap();
+        assert_eq!(pattern, vec![10.0, 5.0, 20.0, 10.0, 5.0, 20.0]);
+        assert_spans(&dash_spans(&pattern, 0.0), &[(0.0, 10.0), (15.0, 35.0), (45.0, 50.0), (70.0, 80.0), (85.0, 100.0)]);
+    }
+
+    #[test]
+    fn invalid_dashes() {
+        let (points, colors) = line(&[(0.0, 0.0), (100.0, 0.0)]);
+        for dashes in [vec![10.0, -5.0], vec![f32::NAN, 5.0], vec![f32::INFINITY], vec![0.0, 0.0]] {
+            let style = LineStyle::new(2.0).with_dashes(dashes.clone(), 0.0);
+            assert!(stroke(&points, &colors, &style, false).is_err(), "{dashes:?}");
+        }
+        let style = LineStyle::new(2.0).with_dashes(vec![10.0, 5.0], f32::NAN);
+        assert!(stroke(&points, &colors, &style, false).is_err());
+        let style = LineStyle::new(2.0).with_dashes(Vec::new(), 0.0);
+        assert!(stroke(&points, &colors, &style, false).is_ok());
+    }
+
+    #[test]
+    fn tiny_dashes() {
+        let (points, colors) = line(&[(0.0, 0.0), (100.0, 0.0)]);
+        let style = LineStyle::new(2.0).with_dashes(vec![1e-6, 1e-6], 0.0);
+        assert!(stroke(&points, &colors, &style, false).is_err());
+
+        // Dashes far along a line land where the pattern puts them
+        let (points, colors) = line(&[(0.0, 0.0), (100_000.0, 0.0)]);
+        let line = points.into_iter().zip(colors).collect::<Vec<_>>();
+        let dashes = dashes(&line, &[3.0, 7.0], 0.0).unwrap();
+        assert_eq!(dashes.len(), 10_000);
+        let last = &dashes[dashes.len() - 1];
+        assert_eq!((last[0].0.x, last[1].0.x), (99_990.0, 99_993.0));
+    }
+
+    #[test]
+    fn too_many_vertices() {
+        // Each point of a zigzag gets a section of 4 vertices with anti-aliasing
+        let zigzag = (0..20_000)
+            .map(|i|(i as f32 * 10.0, (i % 2) as f32 * 10.0))
+            .collect::<Vec<_>>();
+        let (points, colors) = line(&zigzag);
+        assert!(stroke(&points, &colors, &LineStyle::new(2.0), false).is_err());
+        assert!(stroke(&points[..1000], &colors[..1000], &LineStyle::new(2.0), false).is_ok());
+    }
+}
@@ -0,0 +1,298 @@
+//! Buttons are just a clickable container. The button keeps track of its interaction state from
+//! the window events and picks a style for that state.
+
+
+use bitflags::bitflags;
+use winit::{
+    event::{
+        WindowEvent,
+        ElementState,
+        MouseButton,
+        TouchPhase,
+    },
+    keyboard::{
+        Key,
+        NamedKey,
+    },
+};
+use crate::{
+    math::*,
+    Uuid,
+};
+use super::{
+    container::{
+        Container,
+        ContainerStyle,
+    },
+    layout::Layout,
+    Size,
+};
+
+
+bitflags! {
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+    pub struct ButtonState: u8 {
+        const HOVERED = 1 << 0;
+        const PRESSED = 1 << 1;
+        const FOCUSED = 1 << 2;
+        const DISABLED = 1 << 3;
+    }
+}
+
+/// The style for each state. The state styles are applied on top of `normal` in the order
+/// focused, hovered, pressed, disabled.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ButtonStyles {
+    pub normal: ContainerStyle,
+    pub focused: ContainerStyle,
+    pub hovered: ContainerStyle,
+    pub pressed: ContainerStyle,
+    pub disabled: ContainerStyle,
+}
+impl ButtonStyles {
+    pub fn for_state(&self, state: ButtonState)->ContainerStyle {
+        let mut style = self.normal;
+        if state.contains(ButtonState::FOCUSED) {
+            style.apply(&self.focused);
+        }
+        if state.contains(ButtonState::HOVERED) {
+            style.apply(&self.hovered);
+        }
+        if state.contains(ButtonState::PRESSED) {
+            style.apply(&self.pressed);
+        }
+        if state.contains(ButtonState::DISABLED) {
+            style.apply(&self.disabled);
+        }
+
+        return style;
+    }
+}
+
+/// A button. Keep this between frames so the state isn't lost, and use [`Button::container`] to
+/// put it in the layout tree.
+#[derive(Debug, Clone)]
+pub struct Button {
+    pub id: Uuid,
+    pub state: ButtonState,
+    pub styles: ButtonStyles,
+}
+impl Button {
+    pub fn new()->Self {
+        Button {
+            id: crate::new_uuid(),
+            state: ButtonState::empty(),
+            styles: ButtonStyles::default(),
+        }
+    }
+
+    pub fn with_styles(mut self, styles: ButtonStyles)->Self {
+        self.styles = styles;
+        return self;
+    }
+
+    #[inline]
+    pub fn is_disabled(&self)->bool {
+        self.state.contains(ButtonState::DISABLED)
+    }
+
+    /// Disabling a button clears all of the other states
+    pub fn set_disabled(&mut self, disabled: bool) {
+        if disabled {
+            self.state = ButtonState::DISABLED;
+        } else {
+            self.state.remove(ButtonState::DISABLED);
+        }
+    }
+
+    /// The style for the current state
+    #[inline]
+    pub fn style(&self)->ContainerStyle {
+        self.styles.for_state(self.state)
+    }
+
+    /// A focusable container for this button with the current style. Add the button's contents
+    /// as children.
+    pub fn container(&self, width: Size, height: Size)->Container {
+        Container::new(width, height)
+            .with_id(self.id)
+            .with_focusable(!self.is_disabled())
+            .with_style(self.style())
+    }
+
+    /// Set the focused state from the focused container, like
+    /// [`EventDispatcher::focused`](super::event::EventDispatcher::focused). This is the only
+    /// place the focused state is set.
+    pub fn sync_focus(&mut self, focused: Option<Uuid>) {
+        if self.is_disabled() {
+            return;
+        }
+        self.state.set(ButtonState::FOCUSED, focused == Some(self.id));
+    }
+
+    /// Update the state from a window event. `layout` is the last layout the button was in.
+    /// Returns `true` if the button was clicked: pressed and released while the pointer was
+    /// inside of it. When focused, Enter clicks the button on press and Space clicks it on
+    /// release.
+    pub fn handle_event(&mut self, ev: &WindowEvent, layout: &Layout)->bool {
+        if self.is_disabled() {
+            return false;
+        }
+
+        match ev {
+            WindowEvent::CursorMoved{position, ..}=>{
+                let point = Point2::new(position.x as f32, position.y as f32);
+                self.state.set(ButtonState::HOVERED, self.is_under(layout, point));
+            },
+            WindowEvent::CursorLeft{..}=>self.state.remove(ButtonState::HOVERED),
+            WindowEvent::MouseInput{state, button: MouseButton::Left, ..}=>{
+                let hovered = self.state.contains(ButtonState::HOVERED);
+                return self.press_or_release(*state == ElementState::Pressed, hovered);
+            },
+            WindowEvent::Touch(touch)=>{
+                let point = Point2::new(touch.location.x as f32, touch.location.y as f32);
+                let inside = self.is_under(layout, point);
+                match touch.phase {
+                    TouchPhase::Started=>return self.press_or_release(true, inside),
+                    TouchPhase::Moved=>self.state.set(ButtonState::HOVERED, inside),
+                    TouchPhase::Ended=>{
+                        let clicked = self.press_or_release(false, inside);
+                        self.state.remove(ButtonState::HOVERED);
+                        return clicked;
+                    },
+                    TouchPhase::Cancelled=>self.state.remove(ButtonState::PRESSED|ButtonState::HOVERED),
+                }
+            },
+            WindowEvent::KeyboardInput{event, ..} if self.state.contains(ButtonState::FOCUSED)=>{
+                let pressed = event.state == ElementState::Pressed;
+                match &event.logical_key {
+                    Key::Named(NamedKey::Enter)=>return pressed && !event.repeat,
+                    Key::Named(NamedKey::Space)=>{
+                        if pressed {
+                            self.state.insert(ButtonState::PRESSED);
+                            return false;
+                        }
+                        let clicked = self.state.contains(ButtonState::PRESSED);
+                        self.state.remove(ButtonState::PRESSED);
+                        return clicked;
+                    },
+                    _=>{},
+                }
+            },
+            WindowEvent::Focused(false)=>self.state.remove(ButtonState::PRESSED|ButtonState::HOVERED),
+            _=>{},
+        }
+
+        return false;
+    }
+
+    /// Is the topmost node at `point` this button or one of its contents. Parts of the button
+    /// that are clipped or covered by other containers don't count.
+    fn is_under(&self, layout: &Layout, point: Point2)->bool {
+        layout.hit_test(point)
+            .is_some_and(|hit|layout.ancestors(hit).any(|i|layout.nodes[i].id == self.id))
+    }
+
+    /// A release is a click if the press was on this button and the release is inside
+    fn press_or_release(&mut self, pressed: bool, inside: bool)->bool {
+        self.state.set(ButtonState::HOVERED, inside);
+        if pressed {
+            self.state.set(ButtonState::PRESSED, inside);
+            return false;
+        }
+
+        let clicked = self.state.contains(ButtonState::PRESSED) && inside;
+        self.state.remove(ButtonState::PRESSED);
+
+        return clicked;
+    }
+}
+impl Default for Button {
+    fn default()->Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::DeviceId;
+    use crate::ui::{
+        container::{
+            Overflow,
+            Placement,
+        },
+        Rect,
+    };
+
+    fn moved(x: f32, y: f32)->WindowEvent {
+        WindowEvent::CursorMoved {
+            device_id: DeviceId::dummy(),
+            position: (x as f64, y as f64).into(),
+        }
+    }
+
+    fn click(button: &mut Button, layout: &Layout, x: f32, y: f32)->bool {
+        button.handle_event(&moved(x, y), layout);
+        let mut clicked = false;
+        for state in [ElementState::Pressed, ElementState::Released] {
+            clicked = button.handle_event(&WindowEvent::MouseInput {
+                device_id: DeviceId::dummy(),
+                state,
+                button: MouseButton::Left,
+            }, layout);
+        }
+        return clicked;
+    }
+
+    #[test]
+    fn hover_and_click_contents() {
+        let mut button = Button::new();
+        let label = Container::new(Size::Pixels(20), Size::Pixels(20));
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_child(button.container(Size::Pixels(100), Size::Pixels(50)).with_child(label));
+        let layout = Layout::compute(&root, Rect::new(0.0, 0.0, 200.0, 200.0));
+
+        button.handle_event(&moved(10.0, 10.0), &layout);
+        assert!(button.state.contains(ButtonState::HOVERED));
+        button.handle_event(&moved(50.0, 100.0), &layout);
+        assert!(!button.state.contains(ButtonState::HOVERED));
+
+        assert!(click(&mut button, &layout, 10.0, 10.0));
+        assert!(click(&mut button, &layout, 90.0, 40.0));
+        assert!(!click(&mut button, &layout, 150.0, 10.0));
+    }
+
+    #[test]
+    fn clipped_part_is_outside() {
+        let mut button = Button::new();
+        let clip = Container::new(Size::Pixels(100), Size::Pixels(50))
+            .with_overflow(Overflow::Clip)
+            .with_child(button.container(Size::Pixels(100), Size::Pixels(100)));
+        let root = Container::new(Size::Fill, Size::Fill).with_child(clip);
+        let layout = Layout::compute(&root, Rect::new(0.0, 0.0, 200.0, 200.0));
+
+        assert!(layout.rect(button.id).unwrap().contains(Point2::new(50.0, 80.0)));
+        button.handle_event(&moved(50.0, 80.0), &layout);
+        assert!(!button.state.contains(ButtonState::HOVERED));
+        assert!(!click(&mut button, &layout, 50.0, 80.0));
+        assert!(click(&mut button, &layout, 50.0, 20.0));
+    }
+
+    #[test]
+    fn covered_part_is_outside() {
+        let mut button = Button::new();
+        let overlay = Container::new(Size::Pixels(50), Size::Pixels(50))
+            .with_placement(Placement::Floating {x: 0.0, y: 0.0, z: 1});
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_child(button.container(Size::Pixels(100), Size::Pixels(50)))
+            .with_child(overlay);
+        let layout = Layout::compute(&root, Rect::new(0.0, 0.0, 200.0, 200.0));
+
+        button.handle_event(&moved(10.0, 10.0), &layout);
+        assert!(!button.state.contains(ButtonState::HOVERED));
+        assert!(!click(&mut button, &layout, 10.0, 10.0));
+        assert!(click(&mut button, &layout, 80.0, 10.0));
+    }
+}
@@ -1,13 +1,291 @@
-use crate::Color;
-use super::Size;
+use serde::{
+    Serialize,
+    Deserialize,
+};
+use crate::{
+    math::*,
+    Color,
+    Uuid,
+};
+use super::{
+    Size,
+    Edges,
+    Corners,
+    Shadow,
+};
 
 
+/// How a container places its children
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum LayoutMode {
+    /// Left to right
+    #[default]
+    Horizontal,
+    /// Top to bottom
+    Vertical,
+    /// Rows and columns of cells. Children are placed with [`Container::cell`].
+    Grid(Grid),
+    /// Children are docked to the edges in insertion order. Each one takes its space from what is
+    /// left over by the children before it. See [`Placement::Dock`].
+    Dock,
+}
+
+/// What happens to children that go outside of the container
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Overflow {
+    #[default]
+    Visible,
+    /// Children are only drawn and hit inside of the container
+    Clip,
+    /// Clip, and scroll the children horizontally by [`Container::scroll`]
+    ScrollX,
+    /// Clip, and scroll the children vertically by [`Container::scroll`]
+    ScrollY,
+    /// Clip, and scroll the children in both directions
+    Scroll,
+}
+impl Overflow {
+    #[inline]
+    pub fn clips(&self)->bool {
+        *self != Overflow::Visible
+    }
+
+    #[inline]
+    pub fn scrolls_x(&self)->bool {
+        matches!(self, Overflow::ScrollX|Overflow::Scroll)
+    }
+
+    #[inline]
+    pub fn scrolls_y(&self)->bool {
+        matches!(self, Overflow::ScrollY|Overflow::Scroll)
+    }
+}
+
+/// How a container is positioned inside of its parent
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Placement {
+    /// Placed by the parent's [`LayoutMode`]
+    #[default]
+    Layout,
+    /// Docked to an edge of the parent. Only used when the parent is [`LayoutMode::Dock`].
+    Dock(DockEdge),
+    /// Taken out of the parent's layout and put at an absolute position in window pixels. Higher
+    /// `z` values are above lower ones, and children inherit the `z` of their parent.
+    Floating {
+        x: f32,
+        y: f32,
+        z: i32,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DockEdge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    /// Take all of the remaining space
+    Center,
+}
+
+/// The row and column tracks of a grid. The container's `gap` is used between both rows and
+/// columns.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Grid {
+    pub rows: Vec<Size>,
+    pub columns: Vec<Size>,
+}
+impl Grid {
+    pub fn new(rows: impl Into<Vec<Size>>, columns: impl Into<Vec<Size>>)->Self {
+        Grid {
+            rows: rows.into(),
+            columns: columns.into(),
+        }
+    }
+}
+
+/// Where a child goes in a [`Grid`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GridCell {
+    pub row: usize,
+    pub column: usize,
+    /// How many rows this child covers. Should be at least 1.
+    pub row_span: usize,
+    /// How many columns this child covers. Should be at least 1.
+    pub column_span: usize,
+}
+impl GridCell {
+    pub const fn new(row: usize, column: usize)->Self {
+        GridCell {
+            row,
+            column,
+            row_span: 1,
+            column_span: 1,
+        }
+    }
+
+    pub const fn span(mut self, rows: usize, columns: usize)->Self {
+        self.row_span = rows;
+        self.column_span = columns;
+        return self;
+    }
+}
+
 pub struct Container {
+    /// Used to find this container in the computed [`Layout`](super::layout::Layout). Set this to
+    /// something stable if the container is rebuilt every frame.
+    pub id: Uuid,
     pub width: Size,
     pub height: Size,
+    pub layout: LayoutMode,
+    /// Space between the edges of this container and its children
+    pub padding: Edges,
+    /// Space between each child along the main axis
+    pub gap: u32,
+    pub placement: Placement,
+    pub overflow: Overflow,
+    /// How far the children are scrolled in pixels. Only used on the axes the [`Overflow`] mode
+    /// scrolls. Usually set from a [`ScrollView`](super::scroll::ScrollView).
+    pub scroll: Vec2,
+    /// Can this container get keyboard focus
+    pub focusable: bool,
+    /// Overrides the Tab order. Containers with a positive index come first (lowest first), then
+    /// containers without one in tree order. Negative indices can be focused by clicking but are
+    /// skipped by Tab.
+    pub tab_index: Option<i32>,
+    /// Names of the classes in the [`ClassRegistry`](super::style::ClassRegistry) to apply to this
+    /// container. Later classes override earlier ones.
+    pub classes: Vec<String>,
+    /// Applied after all of the classes
+    pub style: ContainerStyle,
+    /// The cell this container occupies when its parent is a grid. If this is `None`, then it is
+    /// put in the next free cell.
+    pub cell: Option<GridCell>,
+    pub children: Vec<Container>,
 }
+impl Container {
+    pub fn new(width: Size, height: Size)->Self {
+        Container {
+            id: crate::new_uuid(),
+            width,
+            height,
+            layout: LayoutMode::default(),
+            padding: Edges::ZERO,
+            gap: 0,
+            placement: Placement::Layout,
+            overflow: Overflow::Visible,
+            scroll: Vec2::zero(),
+            focusable: false,
+            tab_index: None,
+            classes: Vec::new(),
+            style: ContainerStyle::default(),
+            cell: None,
+            children: Vec::new(),
+        }
+    }
 
+    pub fn with_id(mut self, id: Uuid)->Self {
+        self.id = id;
+        return self;
+    }
+
+    pub fn with_layout(mut self, layout: LayoutMode)->Self {
+        self.layout = layout;
+        return self;
+    }
+
+    pub fn with_padding(mut self, padding: Edges)->Self {
+        self.padding = padding;
+        return self;
+    }
+
+    pub fn with_gap(mut self, gap: u32)->Self {
+        self.gap = gap;
+        return self;
+    }
+
+    pub fn with_placement(mut self, placement: Placement)->Self {
+        self.placement = placement;
+        return self;
+    }
+
+    pub fn with_overflow(mut self, overflow: Overflow)->Self {
+        self.overflow = overflow;
+        return self;
+    }
+
+    pub fn with_scroll(mut self, scroll: Vec2)->Self {
+        self.scroll = scroll;
+        return self;
+    }
+
+    pub fn with_focusable(mut self, focusable: bool)->Self {
+        self.focusable = focusable;
+        return self;
+    }
+
+    pub fn with_tab_index(mut self, tab_index: i32)->Self {
+        self.tab_index = Some(tab_index);
+        return self;
+    }
+
+    pub fn with_class(mut self, class: impl Into<String>)->Self {
+        self.classes.push(class.into());
+        return self;
+    }
+
+    pub fn with_style(mut self, style: ContainerStyle)->Self {
+        self.style = style;
+        return self;
+    }
+
+    pub fn with_cell(mut self, cell: GridCell)->Self {
+        self.cell = Some(cell);
+        return self;
+    }
+
+    pub fn with_child(mut self, child: Container)->Self {
+        self.children.push(child);
+        return self;
+    }
+
+    #[inline]
+    pub fn is_floating(&self)->bool {
+        matches!(self.placement, Placement::Floating{..})
+    }
+
+    #[inline]
+    pub fn push(&mut self, child: Container) {
+        self.children.push(child);
+    }
+}
+
+/// A set of style properties. Any property that is `None` is left to the classes before it (or
+/// the defaults in [`ComputedStyle`](super::style::ComputedStyle)).
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ContainerStyle {
     pub border: Option<Color>,
+    pub border_width: Option<u32>,
     pub bg: Option<Color>,
+    /// Overrides [`Container::padding`]
+    pub padding: Option<Edges>,
+    /// Space between the edges of the area given by the parent and this container
+    pub margin: Option<Edges>,
+    pub corner_radius: Option<Corners>,
+    pub shadow: Option<Shadow>,
+    pub text_color: Option<Color>,
+}
+impl ContainerStyle {
+    /// Overwrite the properties of `self` with every property that is set in `other`.
+    pub fn apply(&mut self, other: &Self) {
+        self.border = other.border.or(self.border);
+        self.border_width = other.border_width.or(self.border_width);
+        self.bg = other.bg.or(self.bg);
+        self.padding = other.padding.or(self.padding);
+        self.margin = other.margin.or(self.margin);
+        self.corner_radius = other.corner_radius.or(self.corner_radius);
+        self.shadow = other.shadow.or(self.shadow);
+        self.text_color = other.text_color.or(self.text_color);
+    }
 }
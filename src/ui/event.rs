@@ -0,0 +1,528 @@
+//! Routing window events through the ui tree. Pointer events go to the topmost container under the
+//! pointer (or to the container a held mouse button was pressed on), keyboard events go to the
+//! focused container, and then the event bubbles up to each parent until a handler stops it.
+
+
+use winit::{
+    event::{
+        WindowEvent,
+        ElementState,
+        MouseButton,
+        MouseScrollDelta,
+        TouchPhase,
+        KeyEvent,
+        Ime,
+    },
+    keyboard::{
+        ModifiersState,
+        Key,
+        NamedKey,
+    },
+};
+use crate::{
+    math::*,
+    Uuid,
+};
+use super::layout::Layout;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiEventKind {
+    PointerMoved,
+    /// The pointer moved onto the target. This doesn't bubble.
+    PointerEntered,
+    /// The pointer moved off of the target. This doesn't bubble.
+    PointerLeft,
+    PointerPressed(MouseButton),
+    PointerReleased(MouseButton),
+    /// The phase is only meaningful for touchpads. Mouse wheels always send
+    /// [`TouchPhase::Moved`].
+    Wheel(MouseScrollDelta, TouchPhase),
+    Touch {
+        id: u64,
+        phase: TouchPhase,
+    },
+    Key(KeyEvent),
+    Ime(Ime),
+    /// The target got keyboard focus. This doesn't bubble.
+    FocusGained,
+    /// The target lost keyboard focus. This doesn't bubble.
+    FocusLost,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiEvent {
+    pub kind: UiEventKind,
+    /// The container the event was sent to. This stays the same while the event bubbles.
+    pub target: Uuid,
+    /// The pointer (or touch) position in window pixels, if it is known
+    pub position: Option<Point2>,
+    /// The keyboard modifiers when the event happened
+    pub modifiers: ModifiersState,
+}
+
+/// Returned by event handlers to say if the event should keep bubbling
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Propagation {
+    #[default]
+    Continue,
+    Stop,
+}
+
+/// Turns window events into [`UiEvent`]s and sends them through the tree. Keep this between
+/// frames and give it the layout from the last frame.
+#[derive(Debug, Clone, Default)]
+pub struct EventDispatcher {
+    pub pointer: Option<Point2>,
+    /// The topmost container under the pointer
+    pub hovered: Option<Uuid>,
+    /// The container a mouse button was pressed on and the button. It gets every pointer move and
+    /// button event, even outside of its rectangle, until that button is released.
+    pub captured: Option<(Uuid, MouseButton)>,
+    /// The container that gets keyboard events. Pressing a container focuses its closest
+    /// focusable ancestor, and Tab/Shift-Tab move through the focusable containers. Tab is sent to
+    /// the focused container first, and only moves the focus if no handler stops it.
+    pub focused: Option<Uuid>,
+    pub modifiers: ModifiersState,
+}
+impl EventDispatcher {
+    pub fn new()->Self {
+        Self::default()
+    }
+
+    /// Send a window event through the tree. `handler` is called with the ID of each container on
+    /// the path from the target to the root. Returns `true` if the event was sent to a container.
+    pub fn dispatch(
+        &mut self,
+        layout: &Layout,
+        ev: &WindowEvent,
+        mut handler: impl FnMut(Uuid, &UiEvent)->Propagation,
+    )->bool {
+        match ev {
+            WindowEvent::CursorMoved{position, ..}=>{
+                let point = Point2::new(position.x as f32, position.y as f32);
+                self.pointer = Some(point);
+                let hit = layout.hit_test(point);
+                self.update_hover(layout, hit, &mut handler);
+                return self.send_pointer(layout, hit, point, UiEventKind::PointerMoved, &mut handler);
+            },
+            WindowEvent::CursorLeft{..}=>{
+                self.pointer = None;
+                self.update_hover(layout, None, &mut handler);
+            },
+            WindowEvent::ModifiersChanged(modifiers)=>self.modifiers = modifiers.state(),
+            WindowEvent::MouseInput{state, button, ..}=>{
+                let Some(point) = self.pointer else {return false};
+                let hit = layout.hit_test(point);
+                match state {
+                    ElementState::Pressed=>{
+                        self.focus_hit(layout, hit, &mut handler);
+                        if self.captured.is_none() {
+                            self.captured = hit.map(|i|(layout.nodes[i].id, *button));
+                        }
+                        return self.send_pointer(layout, hit, point, UiEventKind::PointerPressed(*button), &mut handler);
+                    },
+                    ElementState::Released=>{
+                        let sent = self.send_pointer(layout, hit, point, UiEventKind::PointerReleased(*button), &mut handler);
+                        if self.captured.is_some_and(|(_, b)|b == *button) {
+                            self.captured = None;
+                        }
+                        return sent;
+                    },
+                }
+            },
+            WindowEvent::MouseWheel{delta, phase, ..}=>{
+                let Some(point) = self.pointer else {return false};
+                let hit = layout.hit_test(point);
+                return self.send_at(layout, hit, point, UiEventKind::Wheel(*delta, *phase), &mut handler);
+            },
+            WindowEvent::Touch(touch)=>{
+                let point = Point2::new(touch.location.x as f32, touch.location.y as f32);
+                let hit = layout.hit_test(point);
+                if touch.phase == TouchPhase::Started {
+                    self.focus_hit(layout, hit, &mut handler);
+                }
+                let kind = UiEventKind::Touch {
+                    id: touch.id,
+                    phase: touch.phase,
+                };
+                return self.send_at(layout, hit, point, kind, &mut handler);
+            },
+            WindowEvent::KeyboardInput{event, ..}=>{
+                let tab = event.state == ElementState::Pressed && event.logical_key == Key::Named(NamedKey::Tab);
+                return self.send_key(layout, UiEventKind::Key(event.clone()), tab, &mut handler);
+            },
+            WindowEvent::Ime(ime)=>{
+                let Some(target) = self.focused else {return false};
+                return self.send_to(layout, target, UiEventKind::Ime(ime.clone()), &mut handler);
+            },
+            _=>{},
+        }
+
+        return false;
+    }
+
+    /// Move the keyboard focus and send [`UiEventKind::FocusLost`] and
+    /// [`UiEventKind::FocusGained`] if it changed.
+    pub fn set_focus(
+        &mut self,
+        layout: &Layout,
+        focused: Option<Uuid>,
+        handler: &mut impl FnMut(Uuid, &UiEvent)->Propagation,
+    ) {
+        if focused == self.focused {
+            return;
+        }
+
+        let old = std::mem::replace(&mut self.focused, focused);
+        if let Some(old) = old {
+            handler(old, &UiEvent {
+                kind: UiEventKind::FocusLost,
+                target: old,
+                position: self.pointer,
+                modifiers: self.modifiers,
+            });
+        }
+        if let Some(new) = focused.filter(|id|layout.index.contains_key(id)) {
+            handler(new, &UiEvent {
+                kind: UiEventKind::FocusGained,
+                target: new,
+                position: self.pointer,
+                modifiers: self.modifiers,
+            });
+        }
+    }
+
+    /// Send a keyboard event to the focused container. If `tab` is set and no handler stops the
+    /// event, then the focus moves to the next container in Tab order.
+    fn send_key(
+        &mut self,
+        layout: &Layout,
+        kind: UiEventKind,
+        tab: bool,
+        handler: &mut impl FnMut(Uuid, &UiEvent)->Propagation,
+    )->bool {
+        let propagation = self.focused.and_then(|target|{
+            self.send_bubbling(layout, target, kind, handler)
+        });
+
+        if tab && propagation != Some(Propagation::Stop) {
+            let next = layout.next_focus(self.focused, self.modifiers.shift_key());
+            self.set_focus(layout, next, handler);
+            return propagation.is_some() || next.is_some();
+        }
+
+        return propagation.is_some();
+    }
+
+    /// Focus the closest focusable ancestor of the node that was hit, or clear the focus if
+    /// there isn't one.
+    fn focus_hit(&mut self, layout: &Layout, hit: Option<usize>, handler: &mut impl FnMut(Uuid, &UiEvent)->Propagation) {
+        let focused = hit
+            .and_then(|i|layout.focusable_ancestor(i))
+            .map(|i|layout.nodes[i].id);
+        self.set_focus(layout, focused, handler);
+    }
+
+    /// Send an event to `target` and bubble it up to the root
+    #[inline]
+    pub fn send_to(
+        &self,
+        layout: &Layout,
+        target: Uuid,
+        kind: UiEventKind,
+        handler: &mut impl FnMut(Uuid, &UiEvent)->Propagation,
+    )->bool {
+        self.send_bubbling(layout, target, kind, handler).is_some()
+    }
+
+    /// Like [`EventDispatcher::send_to`], but returns whether a handler stopped the event, or
+    /// `None` if `target` isn't in the layout
+    fn send_bubbling(
+        &self,
+        layout: &Layout,
+        target: Uuid,
+        kind: UiEventKind,
+        handler: &mut impl FnMut(Uuid, &UiEvent)->Propagation,
+    )->Option<Propagation> {
+        let index = layout.index.get(&target).copied()?;
+        let ev = UiEvent {
+            kind,
+            target,
+            position: self.pointer,
+            modifiers: self.modifiers,
+        };
+
+        return Some(bubble(layout, index, &ev, handler));
+    }
+
+    /// Send a pointer event to the captured container if there is one, otherwise to the node that
+    /// was hit
+    fn send_pointer(
+        &mut self,
+        layout: &Layout,
+        hit: Option<usize>,
+        point: Point2,
+        kind: UiEventKind,
+        handler: &mut impl FnMut(Uuid, &UiEvent)->Propagation,
+    )->bool {
+        if let Some((target, _)) = self.captured {
+            if layout.index.contains_key(&target) {
+                return self.send_to(layout, target, kind, handler);
+            }
+            // The captured container is gone
+            self.captured = None;
+        }
+
+        return self.send_at(layout, hit, point, kind, handler);
+    }
+
+    /// Send an event to the node that was hit and bubble it up to the root
+    fn send_at(
+        &self,
+        layout: &Layout,
+        hit: Option<usize>,
+        point: Point2,
+        kind: UiEventKind,
+        handler: &mut impl FnMut(Uuid, &UiEvent)->Propagation,
+    )->bool {
+        let Some(index) = hit else {return false};
+        let target = layout.nodes[index].id;
+        let ev = UiEvent {
+            kind,
+            target,
+            position: Some(point),
+            modifiers: self.modifiers,
+        };
+        bubble(layout, index, &ev, handler);
+
+        return true;
+    }
+
+    /// Send enter and leave events if the hovered container changed
+    fn update_hover(&mut self, layout: &Layout, hit: Option<usize>, handler: &mut impl FnMut(Uuid, &UiEvent)->Propagation) {
+        let hovered = hit.map(|i|layout.nodes[i].id);
+        if hovered == self.hovered {
+            return;
+        }
+
+        let old = std::mem::replace(&mut self.hovered, hovered);
+        if let Some(old) = old {
+            handler(old, &UiEvent {
+                kind: UiEventKind::PointerLeft,
+                target: old,
+                position: self.pointer,
+                modifiers: self.modifiers,
+            });
+        }
+        if let Some(new) = hovered {
+            handler(new, &UiEvent {
+                kind: UiEventKind::PointerEntered,
+                target: new,
+                position: self.pointer,
+                modifiers: self.modifiers,
+            });
+        }
+    }
+}
+
+
+/// Returns [`Propagation::Stop`] if a handler stopped the event
+fn bubble(layout: &Layout, index: usize, ev: &UiEvent, handler: &mut impl FnMut(Uuid, &UiEvent)->Propagation)->Propagation {
+    for node in layout.ancestors(index) {
+        if handler(layout.nodes[node].id, ev) == Propagation::Stop {
+            return Propagation::Stop;
+        }
+    }
+
+    return Propagation::Continue;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::DeviceId;
+    use crate::ui::{
+        container::Container,
+        Size,
+        Rect,
+    };
+
+    fn moved(x: f32, y: f32)->WindowEvent {
+        WindowEvent::CursorMoved {
+            device_id: DeviceId::dummy(),
+            position: (x as f64, y as f64).into(),
+        }
+    }
+
+    fn button(state: ElementState)->WindowEvent {
+        WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state,
+            button: MouseButton::Left,
+        }
+    }
+
+    /// Dispatch `ev` and return each (handler ID, target, kind) that was sent
+    fn record(dispatcher: &mut EventDispatcher, layout: &Layout, ev: &WindowEvent, stop_at: Option<Uuid>)->Vec<(Uuid, Uuid, UiEventKind)> {
+        let mut sent = Vec::new();
+        dispatcher.dispatch(layout, ev, |id, ev|{
+            sent.push((id, ev.target, ev.kind.clone()));
+            match stop_at == Some(id) {
+                true=>Propagation::Stop,
+                false=>Propagation::Continue,
+            }
+        });
+        return sent;
+    }
+
+    #[test]
+    fn bubbles_to_ancestors() {
+        let child = Container::new(Size::Pixels(20), Size::Pixels(20));
+        let child_id = child.id;
+        let parent = Container::new(Size::Pixels(50), Size::Pixels(50)).with_child(child);
+        let parent_id = parent.id;
+        let root = Container::new(Size::Fill, Size::Fill).with_child(parent);
+        let layout = Layout::compute(&root, Rect::new(0.0, 0.0, 100.0, 100.0));
+
+        let mut dispatcher = EventDispatcher::new();
+        let sent = record(&mut dispatcher, &layout, &moved(10.0, 10.0), None);
+        assert_eq!(sent, vec![
+            (child_id, child_id, UiEventKind::PointerEntered),
+            (child_id, child_id, UiEventKind::PointerMoved),
+            (parent_id, child_id, UiEventKind::PointerMoved),
+            (root.id, child_id, UiEventKind::PointerMoved),
+        ]);
+
+        // Stopping the event keeps it from the rest of the ancestors
+        let sent = record(&mut dispatcher, &layout, &button(ElementState::Pressed), Some(parent_id));
+        assert_eq!(sent, vec![
+            (child_id, child_id, UiEventKind::PointerPressed(MouseButton::Left)),
+            (parent_id, child_id, UiEventKind::PointerPressed(MouseButton::Left)),
+        ]);
+    }
+
+    #[test]
+    fn capture_follows_pressed_target() {
+        let first = Container::new(Size::Fill, Size::Fill);
+        let first_id = first.id;
+        let second = Container::new(Size::Fill, Size::Fill);
+        let second_id = second.id;
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_child(first)
+            .with_child(second);
+        let layout = Layout::compute(&root, Rect::new(0.0, 0.0, 100.0, 100.0));
+        let to_target = |sent: Vec<(Uuid, Uuid, UiEventKind)>|sent.into_iter()
+            .filter(|(id, target, _)|id == target)
+            .map(|(_, target, kind)|(target, kind))
+            .collect::<Vec<_>>();
+
+        let mut dispatcher = EventDispatcher::new();
+        record(&mut dispatcher, &layout, &moved(10.0, 10.0), None);
+        record(&mut dispatcher, &layout, &button(ElementState::Pressed), None);
+        assert_eq!(dispatcher.captured, Some((first_id, MouseButton::Left)));
+
+        // Hover still follows the pointer, but the moves go to the pressed container
+        let sent = to_target(record(&mut dispatcher, &layout, &moved(80.0, 10.0), None));
+        assert_eq!(sent, vec![
+            (first_id, UiEventKind::PointerLeft),
+            (second_id, UiEventKind::PointerEntered),
+            (first_id, UiEventKind::PointerMoved),
+        ]);
+        let sent = to_target(record(&mut dispatcher, &layout, &moved(200.0, 10.0), None));
+        assert_eq!(sent, vec![
+            (second_id, UiEventKind::PointerLeft),
+            (first_id, UiEventKind::PointerMoved),
+        ]);
+
+        let sent = to_target(record(&mut dispatcher, &layout, &button(ElementState::Released), None));
+        assert_eq!(sent, vec![(first_id, UiEventKind::PointerReleased(MouseButton::Left))]);
+        assert_eq!(dispatcher.captured, None);
+
+        // Once released, moves go to whatever is under the pointer again
+        let sent = to_target(record(&mut dispatcher, &layout, &moved(80.0, 10.0), None));
+        assert_eq!(sent, vec![
+            (second_id, UiEventKind::PointerEntered),
+            (second_id, UiEventKind::PointerMoved),
+        ]);
+    }
+
+    #[test]
+    fn capture_ends_when_target_is_gone() {
+        let first = Container::new(Size::Fill, Size::Fill);
+        let second = Container::new(Size::Fill, Size::Fill);
+        let second_id = second.id;
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_child(first)
+            .with_child(second);
+        let layout = Layout::compute(&root, Rect::new(0.0, 0.0, 100.0, 100.0));
+
+        let mut dispatcher = EventDispatcher::new();
+        record(&mut dispatcher, &layout, &moved(10.0, 10.0), None);
+        record(&mut dispatcher, &layout, &button(ElementState::Pressed), None);
+
+        // The pressed container isn't in the next layout
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_id(root.id)
+            .with_child(Container::new(Size::Fill, Size::Fill).with_id(second_id));
+        let layout = Layout::compute(&root, Rect::new(0.0, 0.0, 100.0, 100.0));
+        let sent = record(&mut dispatcher, &layout, &moved(10.0, 10.0), None);
+        assert!(sent.contains(&(second_id, second_id, UiEventKind::PointerMoved)));
+        assert_eq!(dispatcher.captured, None);
+    }
+
+    /// Press Tab, with `stop_at` stopping the key event. Key events can't be made outside of
+    /// winit, so an IME event stands in for the key.
+    fn press_tab(dispatcher: &mut EventDispatcher, layout: &Layout, stop_at: Option<Uuid>)->Vec<(Uuid, UiEventKind)> {
+        let mut sent = Vec::new();
+        dispatcher.send_key(layout, UiEventKind::Ime(Ime::Enabled), true, &mut |id, ev|{
+            sent.push((id, ev.kind.clone()));
+            match stop_at == Some(id) {
+                true=>Propagation::Stop,
+                false=>Propagation::Continue,
+            }
+        });
+        return sent;
+    }
+
+    #[test]
+    fn tab_goes_to_focused_first() {
+        let first = Container::new(Size::Fill, Size::Fill).with_focusable(true);
+        let first_id = first.id;
+        let second = Container::new(Size::Fill, Size::Fill).with_focusable(true);
+        let second_id = second.id;
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_child(first)
+            .with_child(second);
+        let layout = Layout::compute(&root, Rect::new(0.0, 0.0, 100.0, 100.0));
+
+        // Nothing is focused, so Tab only moves the focus
+        let mut dispatcher = EventDispatcher::new();
+        let sent = press_tab(&mut dispatcher, &layout, None);
+        assert_eq!(sent, vec![(first_id, UiEventKind::FocusGained)]);
+
+        // The key bubbles up from the focused container before the focus moves
+        let key = UiEventKind::Ime(Ime::Enabled);
+        let sent = press_tab(&mut dispatcher, &layout, None);
+        assert_eq!(sent, vec![
+            (first_id, key.clone()),
+            (root.id, key.clone()),
+            (first_id, UiEventKind::FocusLost),
+            (second_id, UiEventKind::FocusGained),
+        ]);
+
+        // A handler that stops the key keeps the focus where it is
+        let sent = press_tab(&mut dispatcher, &layout, Some(second_id));
+        assert_eq!(sent, vec![(second_id, key.clone())]);
+        assert_eq!(dispatcher.focused, Some(second_id));
+        let sent = press_tab(&mut dispatcher, &layout, Some(root.id));
+        assert_eq!(sent, vec![(second_id, key.clone()), (root.id, key)]);
+        assert_eq!(dispatcher.focused, Some(second_id));
+
+        // Shift-Tab goes backwards
+        dispatcher.modifiers = ModifiersState::SHIFT;
+        press_tab(&mut dispatcher, &layout, None);
+        assert_eq!(dispatcher.focused, Some(first_id));
+    }
+}
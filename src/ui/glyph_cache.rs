@@ -0,0 +1,525 @@
+//! A cache of rasterized glyphs. Glyphs are packed into atlas pages that are uploaded as images
+//! to the [`Renderer`]. When every page is full, a new page is added until [`GlyphCache::max_pages`]
+//! is reached, then the least recently used glyphs are evicted.
+
+
+use fontdue::Font;
+use image::{
+    RgbaImage,
+    Rgba,
+};
+use anyhow::Result;
+#[allow(unused)]
+use log::{
+    trace,
+    debug,
+    warn,
+};
+use crate::render::{
+    Renderer,
+    ImageID,
+};
+use super::text::FontID;
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    pub font: FontID,
+    /// The bits of the `f32` font size
+    pub size: u32,
+    pub glyph_index: u16,
+    /// Horizontal offset in steps of `1 / GlyphCache::SUBPIXEL_STEPS` pixels
+    pub subpixel: u8,
+}
+impl GlyphKey {
+    pub fn new(font: FontID, size: f32, glyph_index: u16, subpixel: u8)->Self {
+        GlyphKey {
+            font,
+            size: size.to_bits(),
+            glyph_index,
+            subpixel: subpixel % GlyphCache::SUBPIXEL_STEPS,
+        }
+    }
+}
+
+/// A glyph in the cache. Glyphs without any pixels (like spaces) have a zero size and don't take
+/// up space in a page.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CachedGlyph {
+    /// Index into [`GlyphCache::pages`]
+    pub page: usize,
+    /// Position in the page image in pixels
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Offset of the bitmap's left edge from the (whole pixel) pen position
+    pub xmin: i32,
+    /// Offset of the bitmap's bottom edge from the baseline (positive is up)
+    pub ymin: i32,
+    /// The frame this glyph was last requested in
+    pub last_used: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct GlyphCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+impl GlyphCacheStats {
+    pub fn hit_rate(&self)->f32 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        return self.hits as f32 / total as f32;
+    }
+}
+
+/// A row of glyphs in a page
+#[derive(Debug, Clone)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// Where the next glyph is appended
+    end: u32,
+    /// `(x, width)` of the slots freed by evicted glyphs
+    free: Vec<(u32, u32)>,
+}
+
+/// One atlas image
+pub struct AtlasPage {
+    pub image: RgbaImage,
+    /// The uploaded image. This changes every time new glyphs are uploaded.
+    pub image_id: Option<ImageID>,
+    shelves: Vec<Shelf>,
+    dirty: bool,
+}
+impl AtlasPage {
+    fn new(size: u32)->Self {
+        AtlasPage {
+            image: RgbaImage::new(size, size),
+            image_id: None,
+            shelves: Vec::new(),
+            dirty: true,
+        }
+    }
+
+    /// Find space for a `width` by `height` rectangle (including padding)
+    fn alloc(&mut self, width: u32, height: u32)->Option<(u32, u32)> {
+        let page_width = self.image.width();
+
+        // Don't put small glyphs on very tall shelves
+        let fits = |shelf: &Shelf|shelf.height >= height && shelf.height <= height + height / 2 + 2;
+        for shelf in self.shelves.iter_mut().filter(|s|fits(s)) {
+            if let Some(slot) = shelf.free.iter().position(|(_, w)|*w >= width) {
+                let (x, w) = shelf.free[slot];
+                if w == width {
+                    shelf.free.remove(slot);
+                } else {
+                    shelf.free[slot] = (x + width, w - width);
+                }
+                return Some((x, shelf.y));
+            }
+            if shelf.end + width <= page_width {
+                let x = shelf.end;
+                shelf.end += width;
+                return Some((x, shelf.y));
+            }
+        }
+
+        let y = self.shelves.last()
+            .map(|s|s.y + s.height)
+            .unwrap_or(0);
+        if y + height > self.image.height() || width > page_width {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y,
+            height,
+            end: width,
+            free: Vec::new(),
+        });
+
+        return Some((0, y));
+    }
+
+    /// Give the space back to the shelf at `y` and clear the pixels
+    fn free(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        for py in y..y + height {
+            for px in x..x + width {
+                self.image.put_pixel(px, py, Rgba([0;4]));
+            }
+        }
+        self.dirty = true;
+
+        let Some(shelf) = self.shelves.iter_mut().find(|s|s.y == y) else {return};
+        shelf.free.push((x, width));
+        shelf.free.sort_unstable();
+
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(shelf.free.len());
+        for (x, w) in shelf.free.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.0 + last.1 == x=>last.1 += w,
+                _=>merged.push((x, w)),
+            }
+        }
+        if merged.last().is_some_and(|(x, w)|x + w == shelf.end) {
+            shelf.end = merged.pop().unwrap().0;
+        }
+        shelf.free = merged;
+    }
+}
+
+pub struct GlyphCache {
+    pub pages: Vec<AtlasPage>,
+    /// Width and height of each page
+    pub page_size: u32,
+    /// The cache evicts glyphs instead of adding pages after this many
+    pub max_pages: usize,
+    pub stats: GlyphCacheStats,
+    /// Incremented every time glyphs are evicted or the cache is cleared, so users know to rebuild
+    /// their shapes
+    pub generation: u64,
+    glyphs: fnv::FnvHashMap<GlyphKey, CachedGlyph>,
+    frame: u64,
+}
+impl GlyphCache {
+    pub const SUBPIXEL_STEPS: u8 = 4;
+    /// Space between glyphs so linear filtering doesn't bleed into the neighbors
+    const PADDING: u32 = 1;
+
+    pub fn new(page_size: u32, max_pages: usize)->Self {
+        GlyphCache {
+            pages: Vec::new(),
+            page_size,
+            max_pages: max_pages.max(1),
+            stats: GlyphCacheStats::default(),
+            generation: 0,
+            glyphs: Default::default(),
+            frame: 0,
+        }
+    }
+
+    /// Start a new frame. Glyphs used in the current frame are never evicted.
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    #[inline]
+    pub fn len(&self)->usize {
+        self.glyphs.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self)->bool {
+        self.glyphs.is_empty()
+    }
+
+    /// Split a pen position into a whole pixel and a subpixel step
+    pub fn snap(x: f32)->(f32, u8) {
+        let steps = Self::SUBPIXEL_STEPS as f32;
+        let whole = x.floor();
+        let step = ((x - whole) * steps).round();
+        if step >= steps {
+            return (whole + 1.0, 0);
+        }
+        return (whole, step as u8);
+    }
+
+    /// Get a glyph, rasterizing it if it isn't cached. Returns `None` if there is no space for it
+    /// even after evicting every glyph that wasn't used this frame.
+    /// Glyphs are white with the coverage in the alpha channel, so they can be drawn in any color
+    /// with a tint.
+    pub fn glyph(&mut self, font: &Font, font_id: FontID, size: f32, glyph_index: u16, subpixel: u8)->Option<CachedGlyph> {
+        let key = GlyphKey::new(font_id, size, glyph_index, subpixel);
+        if let Some(glyph) = self.glyphs.get_mut(&key) {
+            glyph.last_used = self.frame;
+            self.stats.hits += 1;
+            return Some(*glyph);
+        }
+        self.stats.misses += 1;
+
+        let (metrics, coverage) = font.rasterize_indexed(glyph_index, size);
+        let (width, coverage) = shift_coverage(
+            coverage,
+            metrics.width,
+            metrics.height,
+            key.subpixel as f32 / Self::SUBPIXEL_STEPS as f32,
+        );
+        let width = width as u32;
+        let height = metrics.height as u32;
+
+        let (page, x, y) = if width == 0 || height == 0 {
+            (0, 0, 0)
+        } else {
+            let (page, x, y) = self.alloc(width + Self::PADDING, height + Self::PADDING)?;
+            let image = &mut self.pages[page].image;
+            for (i, c) in coverage.into_iter().enumerate() {
+                image.put_pixel(x + i as u32 % width, y + i as u32 / width, Rgba([255, 255, 255, c]));
+            }
+            self.pages[page].dirty = true;
+            (page, x, y)
+        };
+
+        let glyph = CachedGlyph {
+            page,
+            x,
+            y,
+            width,
+            height,
+            xmin: metrics.xmin,
+            ymin: metrics.ymin,
+            last_used: self.frame,
+        };
+        self.glyphs.insert(key, glyph);
+
+        return Some(glyph);
+    }
+
+    /// Mark a cached glyph as used this frame, so it isn't evicted. Use this for glyphs that are
+    /// still drawn without calling [`GlyphCache::glyph`] again.
+    pub fn touch(&mut self, key: &GlyphKey) {
+        if let Some(glyph) = self.glyphs.get_mut(key) {
+            glyph.last_used = self.frame;
+        }
+    }
+
+    /// Upload every page that changed since the last upload
+    pub fn upload(&mut self, renderer: &mut Renderer)->Result<()> {
+        for page in self.pages.iter_mut().filter(|p|p.dirty) {
+            if let Some(old) = page.image_id.take() {
+                renderer.drop_image(old);
+            }
+            page.image_id = Some(renderer.upload_image(page.image.clone())?);
+            page.dirty = false;
+        }
+
+        return Ok(());
+    }
+
+    /// Remove every glyph and drop the uploaded pages
+    pub fn clear(&mut self, renderer: &mut Renderer) {
+        for page in self.pages.drain(..) {
+            if let Some(id) = page.image_id {
+                renderer.drop_image(id);
+            }
+        }
+        self.glyphs.clear();
+        self.generation += 1;
+    }
+
+    fn alloc(&mut self, width: u32, height: u32)->Option<(usize, u32, u32)> {
+        loop {
+            for (i, page) in self.pages.iter_mut().enumerate() {
+                if let Some((x, y)) = page.alloc(width, height) {
+                    return Some((i, x, y));
+                }
+            }
+
+            if self.pages.len() < self.max_pages {
+                debug!("Adding glyph cache page #{}", self.pages.len() + 1);
+                self.pages.push(AtlasPage::new(self.page_size));
+                continue;
+            }
+
+            if !self.evict_lru() {
+                warn!("Glyph cache is full");
+                return None;
+            }
+        }
+    }
+
+    /// Evict the least recently used glyph that wasn't used this frame. Zero-sized glyphs are
+    /// never evicted, since they don't take up any space.
+    fn evict_lru(&mut self)->bool {
+        let oldest = self.glyphs.iter()
+            .filter(|(_, g)|g.last_used < self.frame && g.width > 0 && g.height > 0)
+            .min_by_key(|(_, g)|g.last_used)
+            .map(|(key, _)|*key);
+        let Some(key) = oldest else {return false};
+
+        let glyph = self.glyphs.remove(&key).unwrap();
+        self.pages[glyph.page].free(
+            glyph.x,
+            glyph.y,
+            glyph.width + Self::PADDING,
+            glyph.height + Self::PADDING,
+        );
+        self.stats.evictions += 1;
+        self.generation += 1;
+
+        return true;
+    }
+}
+
+
+/// Move a coverage bitmap right by `offset` (less than a pixel) with linear filtering. The bitmap
+/// gets one pixel wider if it is shifted.
+fn shift_coverage(coverage: Vec<u8>, width: usize, height: usize, offset: f32)->(usize, Vec<u8>) {
+    if offset <= 0.0 || width == 0 {
+        return (width, coverage);
+    }
+
+    let new_width = width + 1;
+    let mut shifted = vec![0; new_width * height];
+    for y in 0..height {
+        let row = &coverage[y * width..(y + 1) * width];
+        for x in 0..new_width {
+            let here = row.get(x).copied().unwrap_or(0) as f32;
+            let left = x.checked_sub(1).map(|x|row[x]).unwrap_or(0) as f32;
+            shifted[y * new_width + x] = (here * (1.0 - offset) + left * offset).round() as u8;
+        }
+    }
+
+    return (new_width, shifted);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Uuid;
+
+    /// Put a glyph in the cache as if it was rasterized at `(x, y)` on page 0
+    fn insert(cache: &mut GlyphCache, glyph_index: u16, x: u32, y: u32, size: u32)->GlyphKey {
+        let key = GlyphKey::new(FontID(Uuid::nil()), 16.0, glyph_index, 0);
+        cache.glyphs.insert(key, CachedGlyph {
+            page: 0,
+            x,
+            y,
+            width: size,
+            height: size,
+            xmin: 0,
+            ymin: 0,
+            last_used: cache.frame,
+        });
+        return key;
+    }
+
+    #[test]
+    fn page_shelves() {
+        let mut page = AtlasPage::new(64);
+        assert_eq!(page.alloc(10, 10), Some((0, 0)));
+        assert_eq!(page.alloc(10, 10), Some((10, 0)));
+        // Too tall for the first shelf
+        assert_eq!(page.alloc(10, 20), Some((0, 10)));
+        // Shorter glyphs fit on a shelf that isn't much taller
+        assert_eq!(page.alloc(10, 8), Some((20, 0)));
+        // But not on one that is
+        assert_eq!(page.alloc(10, 5), Some((0, 30)));
+        // A full shelf starts a new one
+        assert_eq!(page.alloc(40, 10), Some((0, 35)));
+    }
+
+    #[test]
+    fn page_full() {
+        let mut page = AtlasPage::new(32);
+        assert_eq!(page.alloc(40, 1), None);
+        assert_eq!(page.alloc(1, 40), None);
+        assert_eq!(page.alloc(32, 32), Some((0, 0)));
+        assert_eq!(page.alloc(1, 1), None);
+    }
+
+    #[test]
+    fn page_free_reuses_space() {
+        let mut page = AtlasPage::new(64);
+        for x in [0, 10, 20, 30] {
+            assert_eq!(page.alloc(10, 10), Some((x, 0)));
+        }
+        page.image.put_pixel(12, 3, Rgba([255; 4]));
+        page.dirty = false;
+
+        page.free(10, 0, 10, 10);
+        assert_eq!(page.image.get_pixel(12, 3), &Rgba([0; 4]));
+        assert!(page.dirty);
+        // Smaller glyphs take part of a freed slot
+        assert_eq!(page.alloc(4, 10), Some((10, 0)));
+        assert_eq!(page.alloc(6, 10), Some((14, 0)));
+
+        // Neighboring slots are merged
+        page.free(0, 0, 10, 10);
+        page.free(10, 0, 4, 10);
+        assert_eq!(page.alloc(14, 10), Some((0, 0)));
+
+        // Slots at the end of the shelf give the space back to the end
+        page.free(30, 0, 10, 10);
+        page.free(20, 0, 10, 10);
+        assert_eq!(page.alloc(44, 10), Some((20, 0)));
+    }
+
+    #[test]
+    fn cache_evicts_when_full() {
+        let mut cache = GlyphCache::new(16, 1);
+        assert_eq!(cache.alloc(16, 16), Some((0, 0, 0)));
+        let old = insert(&mut cache, 1, 0, 0, 15);
+        let space = insert(&mut cache, 2, 0, 0, 0);
+
+        // Glyphs used this frame are never evicted
+        assert_eq!(cache.alloc(16, 16), None);
+        assert_eq!(cache.generation, 0);
+
+        cache.begin_frame();
+        assert_eq!(cache.alloc(16, 16), Some((0, 0, 0)));
+        assert_eq!(cache.stats.evictions, 1);
+        assert_eq!(cache.generation, 1);
+        assert!(!cache.glyphs.contains_key(&old));
+        // Zero-size glyphs don't take any space, so they stay
+        assert!(cache.glyphs.contains_key(&space));
+        assert_eq!(cache.pages.len(), 1);
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used() {
+        let mut cache = GlyphCache::new(16, 1);
+        cache.alloc(8, 16);
+        cache.alloc(8, 16);
+        let first = insert(&mut cache, 1, 0, 0, 7);
+        cache.begin_frame();
+        let second = insert(&mut cache, 2, 8, 0, 7);
+        cache.begin_frame();
+        cache.touch(&first);
+        cache.begin_frame();
+
+        assert_eq!(cache.alloc(8, 16), Some((0, 8, 0)));
+        assert!(cache.glyphs.contains_key(&first));
+        assert!(!cache.glyphs.contains_key(&second));
+    }
+
+    #[test]
+    fn cache_adds_pages() {
+        let mut cache = GlyphCache::new(16, 2);
+        assert_eq!(cache.alloc(16, 16), Some((0, 0, 0)));
+        assert_eq!(cache.alloc(16, 16), Some((1, 0, 0)));
+        assert_eq!(cache.alloc(16, 16), None);
+        assert_eq!(cache.generation, 0);
+    }
+
+    #[test]
+    fn snap() {
+        assert_eq!(GlyphCache::snap(3.0), (3.0, 0));
+        assert_eq!(GlyphCache::snap(3.1), (3.0, 0));
+        assert_eq!(GlyphCache::snap(3.25), (3.0, 1));
+        assert_eq!(GlyphCache::snap(3.5), (3.0, 2));
+        assert_eq!(GlyphCache::snap(3.7), (3.0, 3));
+        // Rounding up past the last step moves to the next pixel
+        assert_eq!(GlyphCache::snap(3.9), (4.0, 0));
+        assert_eq!(GlyphCache::snap(-0.25), (-1.0, 3));
+
+        let key = GlyphKey::new(FontID(Uuid::nil()), 16.0, 0, GlyphCache::SUBPIXEL_STEPS + 1);
+        assert_eq!(key.subpixel, 1);
+    }
+
+    #[test]
+    fn shift() {
+        assert_eq!(shift_coverage(vec![255], 1, 1, 0.0), (1, vec![255]));
+        assert_eq!(shift_coverage(Vec::new(), 0, 4, 0.5), (0, Vec::new()));
+
+        assert_eq!(shift_coverage(vec![255], 1, 1, 0.25), (2, vec![191, 64]));
+        assert_eq!(
+            shift_coverage(vec![0, 255, 255, 0], 2, 2, 0.5),
+            (3, vec![0, 128, 128, 128, 128, 0]),
+        );
+    }
+}
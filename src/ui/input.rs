@@ -0,0 +1,932 @@
+//! An editable text field. It is built on [`Text`] for display and takes its input from the
+//! [`UiEvent`]s sent by the [`EventDispatcher`](super::event::EventDispatcher).
+
+
+use winit::{
+    event::{
+        ElementState,
+        MouseButton,
+        MouseScrollDelta,
+        Ime,
+    },
+    keyboard::{
+        Key,
+        NamedKey,
+    },
+    window::Window,
+    dpi::{
+        PhysicalPosition,
+        PhysicalSize,
+    },
+};
+use anyhow::{
+    Result,
+    bail,
+};
+#[allow(unused)]
+use log::{
+    trace,
+    warn,
+};
+use std::{
+    ops::Range,
+    sync::Arc,
+};
+use crate::{
+    render::{
+        Renderer,
+        RenderFrame,
+    },
+    math::*,
+    Uuid,
+    Color,
+};
+use super::{
+    event::{
+        UiEvent,
+        UiEventKind,
+    },
+    text::{
+        Text,
+        TextLayout,
+        TextStyle,
+        Fonts,
+    },
+    glyph_cache::GlyphCache,
+    container::Container,
+    Size,
+    Rect,
+};
+
+
+/// Somewhere to copy text to and paste text from
+pub trait Clipboard {
+    fn get_text(&mut self)->Option<String>;
+    fn set_text(&mut self, text: &str);
+}
+impl Clipboard for arboard::Clipboard {
+    fn get_text(&mut self)->Option<String> {
+        arboard::Clipboard::get_text(self).ok()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        if let Err(e) = arboard::Clipboard::set_text(self, text) {
+            warn!("Could not set the clipboard: {e}");
+        }
+    }
+}
+/// A clipboard that only lives in memory
+impl Clipboard for String {
+    fn get_text(&mut self)->Option<String> {
+        Some(self.clone())
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.clear();
+        self.push_str(text);
+    }
+}
+
+/// The state saved for undo and redo
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshot {
+    text: String,
+    cursor: usize,
+    anchor: Option<usize>,
+}
+
+/// Used to merge runs of typing (or deleting) into a single undo step
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum EditKind {
+    Other,
+    Typing,
+    Deleting,
+}
+
+/// An in-progress IME composition
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preedit {
+    pub text: String,
+    /// The byte range of the IME's cursor in `text`
+    pub cursor: Option<(usize, usize)>,
+}
+
+pub struct TextInput {
+    pub id: Uuid,
+    pub text: String,
+    /// Allow line breaks. Multi-line inputs wrap and scroll vertically, single-line inputs scroll
+    /// horizontally.
+    pub multiline: bool,
+    /// Byte offset of the caret
+    pub cursor: usize,
+    /// The other end of the selection
+    pub anchor: Option<usize>,
+    pub preedit: Option<Preedit>,
+    /// How far the content is scrolled in pixels
+    pub scroll: Vec2,
+    pub max_undo: usize,
+    pub selection_color: Color,
+    /// Seconds the caret is shown, then hidden, while the input is focused. Zero or less doesn't
+    /// blink.
+    pub blink_interval: f32,
+    /// The window to turn IME input on and off in when the input gets and loses focus. This is
+    /// taken from the renderer in [`TextInput::prepare`] if it isn't set.
+    pub window: Option<Arc<Window>>,
+    pub display: Text,
+    /// The layout and rectangle from the last call to [`TextInput::prepare`]
+    pub layout: TextLayout,
+    pub rect: Rect,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    last_edit: EditKind,
+    dragging: bool,
+    /// Remembered x position when moving up and down between lines
+    preferred_x: Option<f32>,
+    focused: bool,
+    /// Seconds since the caret last moved
+    blink: f32,
+}
+impl TextInput {
+    pub fn new(style: TextStyle, multiline: bool)->Self {
+        let mut display = Text::new("", style);
+        display.wrap = multiline;
+        TextInput {
+            id: crate::new_uuid(),
+            text: String::new(),
+            multiline,
+            cursor: 0,
+            anchor: None,
+            preedit: None,
+            scroll: Vec2::zero(),
+            max_undo: 100,
+            selection_color: Color(0.2, 0.4, 0.9, 0.4),
+            blink_interval: 0.5,
+            window: None,
+            display,
+            layout: TextLayout::default(),
+            rect: Rect::default(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_edit: EditKind::Other,
+            dragging: false,
+            preferred_x: None,
+            focused: false,
+            blink: 0.0,
+        }
+    }
+
+    pub fn with_text(mut self, text: impl Into<String>)->Self {
+        self.set_text(text);
+        return self;
+    }
+
+    /// Replace the text. This clears the undo history.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.cursor = self.text.len();
+        self.anchor = None;
+        self.undo.clear();
+        self.redo.clear();
+        self.last_edit = EditKind::Other;
+    }
+
+    /// A focusable container for this input
+    pub fn container(&self, width: Size, height: Size)->Container {
+        Container::new(width, height)
+            .with_id(self.id)
+            .with_focusable(true)
+    }
+
+    /// The selected byte range, if there is one
+    pub fn selection(&self)->Option<Range<usize>> {
+        let anchor = self.anchor.filter(|a|*a != self.cursor)?;
+        return Some(anchor.min(self.cursor)..anchor.max(self.cursor));
+    }
+
+    #[inline]
+    pub fn selected_text(&self)->Option<&str> {
+        self.selection().map(|range|&self.text[range])
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.text.len();
+    }
+
+    /// Does this input have keyboard focus. This follows the [`UiEventKind::FocusGained`] and
+    /// [`UiEventKind::FocusLost`] events.
+    #[inline]
+    pub fn is_focused(&self)->bool {
+        self.focused
+    }
+
+    /// Advance the caret blinking by `dt` seconds. Returns `true` while the input is focused, so
+    /// the window should keep redrawing.
+    pub fn update(&mut self, dt: f32)->bool {
+        if !self.focused {
+            return false;
+        }
+        self.blink += dt.max(0.0);
+
+        return self.blink_interval > 0.0;
+    }
+
+    /// Is the caret drawn right now
+    pub fn caret_visible(&self)->bool {
+        if !self.focused {
+            return false;
+        }
+        if self.blink_interval <= 0.0 {
+            return true;
+        }
+        return ((self.blink / self.blink_interval) as u64).is_multiple_of(2);
+    }
+
+    /// Handle an event sent to this input. Returns `true` if the text changed. Stop the event's
+    /// propagation when it does, so a Tab typed into a multi-line input doesn't also move the
+    /// focus.
+    pub fn handle_event(&mut self, ev: &UiEvent, clipboard: &mut dyn Clipboard)->bool {
+        match &ev.kind {
+            UiEventKind::PointerPressed(MouseButton::Left)=>{
+                let Some(point) = ev.position else {return false};
+                self.blink = 0.0;
+                let offset = self.offset_at(point);
+                if ev.modifiers.shift_key() {
+                    self.anchor.get_or_insert(self.cursor);
+                } else {
+                    self.anchor = Some(offset);
+                }
+                self.cursor = offset;
+                self.dragging = true;
+                self.preferred_x = None;
+                self.last_edit = EditKind::Other;
+            },
+            UiEventKind::PointerMoved if self.dragging=>{
+                let Some(point) = ev.position else {return false};
+                self.cursor = self.offset_at(point);
+            },
+            UiEventKind::PointerReleased(MouseButton::Left)=>self.dragging = false,
+            UiEventKind::FocusGained=>{
+                self.focused = true;
+                self.blink = 0.0;
+                if let Some(window) = &self.window {
+                    window.set_ime_allowed(true);
+                    self.set_ime_cursor_area(window);
+                }
+            },
+            UiEventKind::FocusLost=>{
+                self.focused = false;
+                self.dragging = false;
+                self.preedit = None;
+                if let Some(window) = &self.window {
+                    window.set_ime_allowed(false);
+                }
+            },
+            UiEventKind::Wheel(delta, _)=>{
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y)=>Vec2::new(*x, *y) * self.layout.line_height,
+                    MouseScrollDelta::PixelDelta(pos)=>Vec2::new(pos.x as f32, pos.y as f32),
+                };
+                if self.multiline {
+                    self.scroll.y -= delta.y;
+                } else {
+                    self.scroll.x -= delta.x + delta.y;
+                }
+                self.clamp_scroll();
+            },
+            UiEventKind::Ime(ime)=>return self.handle_ime(ime),
+            UiEventKind::Key(key)=>{
+                if key.state != ElementState::Pressed || self.preedit.is_some() {
+                    return false;
+                }
+                self.blink = 0.0;
+                // Ctrl-Alt is AltGr on some platforms, which types characters instead
+                let alt_gr = ev.modifiers.control_key() && ev.modifiers.alt_key();
+                let shortcut = (ev.modifiers.control_key() && !alt_gr) || ev.modifiers.super_key();
+                let word = ev.modifiers.control_key() || ev.modifiers.alt_key();
+                let shift = ev.modifiers.shift_key();
+
+                match &key.logical_key {
+                    Key::Named(NamedKey::ArrowLeft)=>{
+                        let target = if word {prev_word(&self.text, self.cursor)} else {prev_char(&self.text, self.cursor)};
+                        self.move_cursor(target, shift, true);
+                    },
+                    Key::Named(NamedKey::ArrowRight)=>{
+                        let target = if word {next_word(&self.text, self.cursor)} else {next_char(&self.text, self.cursor)};
+                        self.move_cursor(target, shift, false);
+                    },
+                    Key::Named(NamedKey::ArrowUp)=>self.move_vertical(-1, shift),
+                    Key::Named(NamedKey::ArrowDown)=>self.move_vertical(1, shift),
+                    Key::Named(NamedKey::Home)=>{
+                        let target = if shortcut {0} else {self.line_bounds().start};
+                        self.move_cursor(target, shift, true);
+                    },
+                    Key::Named(NamedKey::End)=>{
+                        let target = if shortcut {self.text.len()} else {self.line_bounds().end};
+                        self.move_cursor(target, shift, false);
+                    },
+                    Key::Named(NamedKey::Escape)=>self.anchor = None,
+                    Key::Named(NamedKey::Backspace)=>{
+                        let start = if word {prev_word(&self.text, self.cursor)} else {prev_char(&self.text, self.cursor)};
+                        return self.delete(start..self.cursor);
+                    },
+                    Key::Named(NamedKey::Delete)=>{
+                        let end = if word {next_word(&self.text, self.cursor)} else {next_char(&self.text, self.cursor)};
+                        return self.delete(self.cursor..end);
+                    },
+                    Key::Named(NamedKey::Enter) if self.multiline=>return self.insert("\n", EditKind::Other),
+                    // Ctrl-Tab and Shift-Tab are left to move the focus
+                    Key::Named(NamedKey::Tab) if self.multiline && !shortcut && !shift=>return self.insert("\t", EditKind::Typing),
+                    Key::Character(c) if shortcut=>match c.to_lowercase().as_str() {
+                        "a"=>self.select_all(),
+                        "c"=>if let Some(text) = self.selected_text() {
+                            clipboard.set_text(text);
+                        },
+                        "x"=>if let Some(text) = self.selected_text() {
+                            clipboard.set_text(text);
+                            return self.insert("", EditKind::Other);
+                        },
+                        "v"=>if let Some(text) = clipboard.get_text() {
+                            let text = if self.multiline {text} else {text.replace(['\r', '\n'], " ")};
+                            return self.insert(&text, EditKind::Other);
+                        },
+                        "z" if shift=>return self.redo(),
+                        "z"=>return self.undo(),
+                        "y"=>return self.redo(),
+                        _=>{},
+                    },
+                    _=>{
+                        let Some(text) = &key.text else {return false};
+                        let text = text.chars()
+                            .filter(|c|!c.is_control())
+                            .collect::<String>();
+                        if !text.is_empty() {
+                            return self.insert(&text, EditKind::Typing);
+                        }
+                    },
+                }
+            },
+            _=>{},
+        }
+
+        return false;
+    }
+
+    pub fn undo(&mut self)->bool {
+        let Some(snapshot) = self.undo.pop() else {return false};
+        let current = self.snapshot();
+        self.redo.push(current);
+        self.restore(snapshot);
+
+        return true;
+    }
+
+    pub fn redo(&mut self)->bool {
+        let Some(snapshot) = self.redo.pop() else {return false};
+        let current = self.snapshot();
+        self.undo.push(current);
+        self.restore(snapshot);
+
+        return true;
+    }
+
+    /// The caret in window pixels, from the last layout
+    pub fn caret_rect(&self)->Rect {
+        let (x, line) = self.caret_position(self.display_offset(self.cursor));
+        let y = self.layout.lines.get(line)
+            .map(|l|l.y)
+            .unwrap_or(0.0);
+        return Rect::new(
+            self.rect.x + x - self.scroll.x,
+            self.rect.y + y - self.scroll.y,
+            1.0,
+            self.layout.line_height,
+        );
+    }
+
+    /// One rectangle for each line of the selection in window pixels, from the last layout
+    pub fn selection_rects(&self)->Vec<Rect> {
+        let Some(range) = self.selection() else {return Vec::new()};
+        let start = self.caret_position(self.display_offset(range.start));
+        let end = self.caret_position(self.display_offset(range.end));
+
+        let mut rects = Vec::new();
+        for line in start.1..=end.1 {
+            let Some(text_line) = self.layout.lines.get(line) else {break};
+            let left = if line == start.1 {start.0} else {0.0};
+            let right = if line == end.1 {end.0} else {text_line.width.max(left)};
+            rects.push(Rect::new(
+                self.rect.x + left - self.scroll.x,
+                self.rect.y + text_line.y - self.scroll.y,
+                right - left,
+                self.layout.line_height,
+            ));
+        }
+
+        return rects;
+    }
+
+    /// Where the IME should put its candidate window. This is given to the window in
+    /// [`TextInput::prepare`] while the input is focused.
+    #[inline]
+    pub fn ime_cursor_area(&self)->Rect {
+        self.caret_rect()
+    }
+
+    /// Lay out the text inside of `rect`, scroll so the caret can be seen, and rebuild the text
+    /// shapes.
+    pub fn prepare(&mut self, renderer: &mut Renderer, fonts: &Fonts, glyphs: &mut GlyphCache, rect: Rect)->Result<()> {
+        let Some(font) = fonts.get(self.display.style.font) else {bail!("Font does not exist")};
+        if self.window.is_none() {
+            self.window = Some(renderer.window.clone());
+        }
+        self.rect = rect;
+        self.display.text = self.display_text();
+        self.display.wrap = self.multiline;
+        self.layout = TextLayout::new(
+            font,
+            &self.display.text,
+            &self.display.style,
+            self.multiline.then_some(rect.width),
+        );
+        self.scroll_to_caret();
+        if let Some(window) = self.window.as_ref().filter(|_|self.focused) {
+            self.set_ime_cursor_area(window);
+        }
+
+        let shifted = Rect {
+            x: rect.x - self.scroll.x,
+            y: rect.y - self.scroll.y,
+            ..rect
+        };
+        return self.display.prepare(renderer, fonts, glyphs, shifted);
+    }
+
+    /// Draw the selection, the text and the caret, clipped to the input's rectangle
+    pub fn draw(&self, frame: &mut RenderFrame)->Result<()> {
+        frame.push_clip(self.rect.to_clip());
+        for rect in self.selection_rects() {
+            frame.draw_rect(Point2::new(rect.x, rect.y), Vec2::new(rect.width, rect.height), self.selection_color);
+        }
+        let result = self.display.draw(frame);
+        if self.caret_visible() {
+            let caret = self.caret_rect();
+            frame.draw_rect(Point2::new(caret.x, caret.y), Vec2::new(caret.width, caret.height), self.display.style.color);
+        }
+        frame.pop_clip();
+
+        return result;
+    }
+
+    fn set_ime_cursor_area(&self, window: &Window) {
+        let area = self.ime_cursor_area();
+        window.set_ime_cursor_area(
+            PhysicalPosition::new(area.x, area.y),
+            PhysicalSize::new(area.width, area.height),
+        );
+    }
+
+    fn handle_ime(&mut self, ime: &Ime)->bool {
+        match ime {
+            Ime::Preedit(text, cursor)=>{
+                self.preedit = (!text.is_empty()).then(||Preedit {
+                    text: text.clone(),
+                    cursor: *cursor,
+                });
+            },
+            Ime::Commit(text)=>{
+                self.preedit = None;
+                return self.insert(text, EditKind::Other);
+            },
+            Ime::Enabled=>{},
+            Ime::Disabled=>self.preedit = None,
+        }
+
+        return false;
+    }
+
+    /// The text with the IME composition inserted at the caret
+    fn display_text(&self)->String {
+        let Some(preedit) = &self.preedit else {return self.text.clone()};
+        let mut text = self.text.clone();
+        text.insert_str(self.cursor, &preedit.text);
+        return text;
+    }
+
+    /// Convert a byte offset in `text` to one in the displayed text
+    fn display_offset(&self, offset: usize)->usize {
+        match &self.preedit {
+            Some(preedit) if offset == self.cursor=>offset + preedit.cursor.map_or(preedit.text.len(), |c|c.1),
+            Some(preedit) if offset > self.cursor=>offset + preedit.text.len(),
+            _=>offset,
+        }
+    }
+
+    /// Convert a byte offset in the displayed text to one in `text`
+    fn text_offset(&self, offset: usize)->usize {
+        match &self.preedit {
+            Some(preedit) if offset > self.cursor=>offset.saturating_sub(preedit.text.len()).max(self.cursor),
+            _=>offset,
+        }
+    }
+
+    /// The byte offset in `text` closest to a point in window pixels
+    fn offset_at(&self, point: Point2)->usize {
+        let local = point - Vec2::new(self.rect.x, self.rect.y) + self.scroll;
+        let Some(last_line) = self.layout.lines.len().checked_sub(1) else {return 0};
+        let line = ((local.y / self.layout.line_height).floor().max(0.0) as usize).min(last_line);
+        let line = &self.layout.lines[line];
+
+        let offset = self.layout.glyphs[line.glyphs.clone()]
+            .iter()
+            .find(|g|g.x + g.advance / 2.0 > local.x)
+            .map(|g|g.byte_offset)
+            .unwrap_or(line.bytes.end);
+
+        return self.text_offset(offset).min(self.text.len());
+    }
+
+    /// The x position and line of a byte offset in the displayed text
+    fn caret_position(&self, offset: usize)->(f32, usize) {
+        if let Some(glyph) = self.layout.glyphs.iter().find(|g|g.byte_offset == offset) {
+            return (glyph.x, glyph.line);
+        }
+
+        for (i, line) in self.layout.lines.iter().enumerate().rev() {
+            if line.bytes.start <= offset && offset <= line.bytes.end {
+                let x = self.layout.glyphs[line.glyphs.clone()]
+                    .last()
+                    .map(|g|g.x + g.advance)
+                    .unwrap_or(0.0);
+                return (x, i);
+            }
+        }
+
+        return (0.0, 0);
+    }
+
+    /// The byte range of the layout line the caret is on
+    fn line_bounds(&self)->Range<usize> {
+        let (_, line) = self.caret_position(self.cursor);
+        match self.layout.lines.get(line) {
+            Some(line)=>line.bytes.clone(),
+            None=>0..self.text.len(),
+        }
+    }
+
+    fn move_cursor(&mut self, target: usize, extend: bool, backwards: bool) {
+        match (extend, self.selection()) {
+            (true, _)=>{
+                self.anchor.get_or_insert(self.cursor);
+                self.cursor = target;
+            },
+            // Collapse the selection to the side the caret is moving towards
+            (false, Some(range))=>{
+                self.cursor = if backwards {range.start} else {range.end};
+                self.anchor = None;
+            },
+            (false, None)=>{
+                self.cursor = target;
+                self.anchor = None;
+            },
+        }
+        self.preferred_x = None;
+        self.last_edit = EditKind::Other;
+    }
+
+    fn move_vertical(&mut self, lines: isize, extend: bool) {
+        let (x, line) = self.caret_position(self.cursor);
+        let x = *self.preferred_x.get_or_insert(x);
+        let Some(target_line) = line.checked_add_signed(lines) else {
+            self.move_cursor(0, extend, true);
+            return;
+        };
+        let Some(target) = self.layout.lines.get(target_line) else {
+            self.move_cursor(self.text.len(), extend, false);
+            return;
+        };
+
+        let y = target.y + self.layout.line_height / 2.0;
+        let point = Point2::new(self.rect.x + x - self.scroll.x, self.rect.y + y - self.scroll.y);
+        let offset = self.offset_at(point);
+
+        if !extend {
+            self.anchor = None;
+        }
+        self.move_cursor(offset, extend, lines < 0);
+        self.preferred_x = Some(x);
+    }
+
+    /// Replace the selection with `text`
+    fn insert(&mut self, text: &str, kind: EditKind)->bool {
+        let range = self.selection().unwrap_or(self.cursor..self.cursor);
+        if range.is_empty() && text.is_empty() {
+            return false;
+        }
+
+        self.push_undo(if range.is_empty() {kind} else {EditKind::Other});
+        self.text.replace_range(range.clone(), text);
+        self.cursor = range.start + text.len();
+        self.anchor = None;
+
+        return true;
+    }
+
+    /// Delete the selection, or `range` if nothing is selected
+    fn delete(&mut self, range: Range<usize>)->bool {
+        let range = self.selection().unwrap_or(range);
+        if range.is_empty() {
+            return false;
+        }
+
+        self.push_undo(EditKind::Deleting);
+        self.text.replace_range(range.clone(), "");
+        self.cursor = range.start;
+        self.anchor = None;
+
+        return true;
+    }
+
+    fn push_undo(&mut self, kind: EditKind) {
+        self.preferred_x = None;
+        if kind != EditKind::Other && kind == self.last_edit {
+            return;
+        }
+        self.last_edit = kind;
+
+        let snapshot = self.snapshot();
+        self.undo.push(snapshot);
+        if self.undo.len() > self.max_undo {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    fn snapshot(&self)->Snapshot {
+        Snapshot {
+            text: self.text.clone(),
+            cursor: self.cursor,
+            anchor: self.anchor,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.text = snapshot.text;
+        self.cursor = snapshot.cursor.min(self.text.len());
+        self.anchor = snapshot.anchor.map(|a|a.min(self.text.len()));
+        self.last_edit = EditKind::Other;
+    }
+
+    fn scroll_to_caret(&mut self) {
+        let (x, line) = self.caret_position(self.display_offset(self.cursor));
+        if self.multiline {
+            let top = line as f32 * self.layout.line_height;
+            let bottom = top + self.layout.line_height;
+            if top < self.scroll.y {
+                self.scroll.y = top;
+            } else if bottom > self.scroll.y + self.rect.height {
+                self.scroll.y = bottom - self.rect.height;
+            }
+        } else if x < self.scroll.x {
+            self.scroll.x = x;
+        } else if x > self.scroll.x + self.rect.width {
+            self.scroll.x = x - self.rect.width;
+        }
+        self.clamp_scroll();
+    }
+
+    fn clamp_scroll(&mut self) {
+        let max_x = if self.multiline {0.0} else {(self.layout.width + 1.0 - self.rect.width).max(0.0)};
+        let max_y = (self.layout.height - self.rect.height).max(0.0);
+        self.scroll.x = self.scroll.x.clamp(0.0, max_x);
+        self.scroll.y = self.scroll.y.clamp(0.0, max_y);
+    }
+}
+
+
+fn prev_char(text: &str, offset: usize)->usize {
+    text[..offset].char_indices()
+        .next_back()
+        .map(|(i, _)|i)
+        .unwrap_or(0)
+}
+
+fn next_char(text: &str, offset: usize)->usize {
+    text[offset..].chars()
+        .next()
+        .map(|c|offset + c.len_utf8())
+        .unwrap_or(text.len())
+}
+
+/// The start of the word before `offset`
+fn prev_word(text: &str, offset: usize)->usize {
+    let mut chars = text[..offset].char_indices().rev().peekable();
+    while chars.next_if(|(_, c)|c.is_whitespace()).is_some() {}
+    let Some((i, c)) = chars.next() else {return 0};
+    let mut start = i;
+    let alnum = c.is_alphanumeric();
+    while let Some((i, _)) = chars.next_if(|(_, c)|!c.is_whitespace() && c.is_alphanumeric() == alnum) {
+        start = i;
+    }
+    return start;
+}
+
+/// The end of the word after `offset`
+fn next_word(text: &str, offset: usize)->usize {
+    let mut chars = text[offset..].char_indices().peekable();
+    while chars.next_if(|(_, c)|c.is_whitespace()).is_some() {}
+    let Some((_, c)) = chars.next() else {return text.len()};
+    let alnum = c.is_alphanumeric();
+    while chars.next_if(|(_, c)|!c.is_whitespace() && c.is_alphanumeric() == alnum).is_some() {}
+    return chars.peek()
+        .map(|(i, _)|offset + i)
+        .unwrap_or(text.len());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::keyboard::ModifiersState;
+    use crate::ui::text::FontID;
+
+    fn text_input(text: &str)->TextInput {
+        TextInput::new(TextStyle::new(FontID(Uuid::nil()), 16.0), false).with_text(text)
+    }
+
+    fn ime(input: &mut TextInput, ime: Ime)->bool {
+        let ev = UiEvent {
+            kind: UiEventKind::Ime(ime),
+            target: input.id,
+            position: None,
+            modifiers: ModifiersState::empty(),
+        };
+        return input.handle_event(&ev, &mut String::new());
+    }
+
+    #[test]
+    fn word_boundaries() {
+        assert_eq!(prev_word("hello world", 11), 6);
+        assert_eq!(prev_word("hello world", 6), 0);
+        assert_eq!(prev_word("hello world", 0), 0);
+        assert_eq!(next_word("hello world", 0), 5);
+        assert_eq!(next_word("hello world", 5), 11);
+        assert_eq!(next_word("hello world", 11), 11);
+
+        // Runs of punctuation are words of their own
+        assert_eq!(next_word("foo.bar", 0), 3);
+        assert_eq!(next_word("foo.bar", 3), 4);
+        assert_eq!(prev_word("foo.bar", 7), 4);
+        assert_eq!(prev_word("foo.bar", 4), 3);
+        assert_eq!(prev_word("a  ...", 6), 3);
+
+        // Offsets are bytes and land on character boundaries
+        assert_eq!(next_word("héllo wörld", 0), 6);
+        assert_eq!(prev_word("héllo wörld", 13), 7);
+        assert_eq!(prev_char("héllo", 3), 1);
+        assert_eq!(next_char("héllo", 1), 3);
+    }
+
+    #[test]
+    fn typing_merges_undo() {
+        let mut input = text_input("");
+        for c in ["a", "b", "c"] {
+            assert!(input.insert(c, EditKind::Typing));
+        }
+        assert_eq!(input.text, "abc");
+
+        assert!(input.undo());
+        assert_eq!(input.text, "");
+        assert!(!input.undo());
+        assert!(input.redo());
+        assert_eq!((input.text.as_str(), input.cursor), ("abc", 3));
+        assert!(!input.redo());
+    }
+
+    #[test]
+    fn deleting_merges_undo() {
+        let mut input = text_input("abc");
+        assert!(input.delete(2..3));
+        assert!(input.delete(1..2));
+        assert_eq!(input.text, "a");
+        assert!(!input.delete(1..1));
+
+        input.undo();
+        assert_eq!((input.text.as_str(), input.cursor), ("abc", 3));
+        assert!(!input.undo());
+    }
+
+    #[test]
+    fn undo_steps() {
+        // Switching between typing and deleting starts a new step
+        let mut input = text_input("");
+        input.insert("ab", EditKind::Typing);
+        input.delete(1..2);
+        input.insert("c", EditKind::Typing);
+        assert_eq!(input.text, "ac");
+        input.undo();
+        assert_eq!(input.text, "a");
+        input.undo();
+        assert_eq!(input.text, "ab");
+        input.undo();
+        assert_eq!(input.text, "");
+
+        // Moving the caret ends a run of typing
+        let mut input = text_input("");
+        input.insert("a", EditKind::Typing);
+        input.move_cursor(0, false, true);
+        input.insert("b", EditKind::Typing);
+        input.undo();
+        assert_eq!(input.text, "a");
+
+        // Pastes are always their own step
+        let mut input = text_input("");
+        input.insert("a", EditKind::Other);
+        input.insert("b", EditKind::Other);
+        input.undo();
+        assert_eq!(input.text, "a");
+    }
+
+    #[test]
+    fn edits_clear_redo() {
+        let mut input = text_input("");
+        input.insert("a", EditKind::Typing);
+        input.undo();
+        input.insert("b", EditKind::Typing);
+        assert!(!input.redo());
+        assert_eq!(input.text, "b");
+    }
+
+    #[test]
+    fn undo_limit() {
+        let mut input = text_input("");
+        input.max_undo = 2;
+        for c in ["a", "b", "c"] {
+            input.insert(c, EditKind::Other);
+        }
+        assert!(input.undo());
+        assert!(input.undo());
+        assert!(!input.undo());
+        assert_eq!(input.text, "a");
+    }
+
+    #[test]
+    fn replace_selection() {
+        let mut input = text_input("hello world");
+        input.anchor = Some(5);
+        input.cursor = 0;
+        assert_eq!(input.selected_text(), Some("hello"));
+
+        input.insert("bye", EditKind::Typing);
+        assert_eq!((input.text.as_str(), input.cursor, input.anchor), ("bye world", 3, None));
+        // Replacing a selection is its own step, even while typing
+        input.insert("!", EditKind::Typing);
+        input.undo();
+        assert_eq!(input.text, "bye world");
+        input.undo();
+        assert_eq!((input.text.as_str(), input.cursor, input.anchor), ("hello world", 0, Some(5)));
+
+        // Deleting takes the selection over the given range
+        input.anchor = Some(5);
+        input.cursor = 11;
+        input.delete(0..1);
+        assert_eq!((input.text.as_str(), input.cursor), ("hello", 5));
+
+        // Cutting a selection leaves nothing
+        input.select_all();
+        assert!(input.insert("", EditKind::Other));
+        assert_eq!(input.text, "");
+        assert!(!input.insert("", EditKind::Other));
+    }
+
+    #[test]
+    fn preedit_offsets() {
+        let mut input = text_input("hello world");
+        input.cursor = 5;
+        assert!(!ime(&mut input, Ime::Preedit("abc".into(), Some((1, 1)))));
+        assert_eq!(input.display_text(), "helloabc world");
+        assert_eq!(input.text, "hello world");
+
+        // Before the composition nothing moves, the caret is inside of it, and after it shifts by
+        // its length
+        assert_eq!(input.display_offset(2), 2);
+        assert_eq!(input.display_offset(5), 6);
+        assert_eq!(input.display_offset(8), 11);
+        assert_eq!(input.text_offset(2), 2);
+        assert_eq!(input.text_offset(7), 5);
+        assert_eq!(input.text_offset(11), 8);
+
+        // Without an IME cursor the caret goes at the end of the composition
+        ime(&mut input, Ime::Preedit("abc".into(), None));
+        assert_eq!(input.display_offset(5), 8);
+
+        assert!(ime(&mut input, Ime::Commit("xyz".into())));
+        assert_eq!(input.preedit, None);
+        assert_eq!((input.text.as_str(), input.cursor), ("helloxyz world", 8));
+        assert_eq!(input.display_offset(8), 8);
+
+        // An empty composition is no composition
+        ime(&mut input, Ime::Preedit(String::new(), None));
+        assert_eq!(input.preedit, None);
+    }
+}
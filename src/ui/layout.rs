@@ -0,0 +1,874 @@
+//! The layout pass. This takes a tree of [`Container`]s and turns it into a flat list of pixel
+//! rectangles. It doesn't touch the renderer, so it can be run without a window.
+
+
+#[allow(unused)]
+use log::{
+    trace,
+    warn,
+};
+use crate::{
+    Uuid,
+    IdMap,
+};
+use super::{
+    container::{
+        Container,
+        LayoutMode,
+        Grid,
+        GridCell,
+        Placement,
+        DockEdge,
+    },
+    style::{
+        ClassRegistry,
+        ComputedStyle,
+        FOCUS_RING_CLASS,
+    },
+    Size,
+    Rect,
+};
+use crate::math::*;
+
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LayoutNode {
+    pub id: Uuid,
+    pub rect: Rect,
+    /// Index of the parent in [`Layout::nodes`]
+    pub parent: Option<usize>,
+    /// Stacking order. Inherited from the parent unless the container is floating.
+    pub z: i32,
+    /// The area this node is visible in, from the closest clipping ancestors. `None` means it
+    /// isn't clipped.
+    pub clip: Option<Rect>,
+    pub focusable: bool,
+    pub tab_index: Option<i32>,
+    pub style: ComputedStyle,
+}
+impl LayoutNode {
+    /// The part of the node that can be seen and clicked
+    pub fn visible_rect(&self)->Rect {
+        match self.clip {
+            Some(clip)=>self.rect.intersect(&clip),
+            None=>self.rect,
+        }
+    }
+
+    pub fn hit(&self, point: Point2)->bool {
+        self.rect.contains(point) && self.clip.is_none_or(|clip|clip.contains(point))
+    }
+}
+
+/// A resolved row or column
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Track {
+    /// Offset from the start of the parent's content area
+    pub start: f32,
+    pub size: f32,
+}
+impl Track {
+    #[inline]
+    pub fn end(&self)->f32 {
+        self.start + self.size
+    }
+}
+
+/// The resolved rows and columns of a grid container
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridTracks {
+    /// The content area of the grid container
+    pub content: Rect,
+    pub rows: Vec<Track>,
+    pub columns: Vec<Track>,
+}
+impl GridTracks {
+    /// The rectangle covered by `cell`, including the gaps between the spanned tracks. Returns
+    /// `None` if the cell is outside of the grid.
+    pub fn cell_rect(&self, cell: GridCell)->Option<Rect> {
+        let row_span = cell.row_span.max(1);
+        let column_span = cell.column_span.max(1);
+        let first_row = self.rows.get(cell.row)?;
+        let last_row = self.rows.get(cell.row + row_span - 1)?;
+        let first_column = self.columns.get(cell.column)?;
+        let last_column = self.columns.get(cell.column + column_span - 1)?;
+
+        return Some(Rect {
+            x: self.content.x + first_column.start,
+            y: self.content.y + first_row.start,
+            width: last_column.end() - first_column.start,
+            height: last_row.end() - first_row.start,
+        });
+    }
+}
+
+/// The scrollable area of a scrolling container
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScrollExtent {
+    /// The content area the children are seen through
+    pub viewport: Rect,
+    /// The size of the children's bounding box (at least the size of the viewport), measured from
+    /// the unscrolled top left of the content area
+    pub content: Vec2,
+}
+impl ScrollExtent {
+    /// The largest scroll offset on each axis
+    pub fn max_offset(&self)->Vec2 {
+        Vec2::new(
+            (self.content.x - self.viewport.width).max(0.0),
+            (self.content.y - self.viewport.height).max(0.0),
+        )
+    }
+}
+
+/// The result of a layout pass. Nodes are stored in tree order (parents before children).
+#[derive(Debug, Default)]
+pub struct Layout {
+    pub nodes: Vec<LayoutNode>,
+    pub index: IdMap<usize>,
+    /// The tracks of every grid container, keyed by the container's ID
+    pub grids: IdMap<GridTracks>,
+    /// The extents of every scrolling container, keyed by the container's ID
+    pub scrolls: IdMap<ScrollExtent>,
+    /// Node indices sorted from bottom to top, computed once per layout
+    draw_order: Vec<usize>,
+}
+impl Layout {
+    /// Lay out `root` inside of `viewport` without any style classes
+    #[inline]
+    pub fn compute(root: &Container, viewport: Rect)->Self {
+        Self::compute_styled(root, viewport, &ClassRegistry::default())
+    }
+
+    /// Lay out `root` inside of `viewport`. Padding and margin from the style classes are used.
+    pub fn compute_styled(root: &Container, viewport: Rect, classes: &ClassRegistry)->Self {
+        let mut pass = LayoutPass {
+            layout: Layout::default(),
+            classes,
+        };
+
+        let rect = Rect {
+            x: viewport.x,
+            y: viewport.y,
+            width: resolve_cross(root.width, viewport.width),
+            height: resolve_cross(root.height, viewport.height),
+        };
+        let z = match root.placement {
+            Placement::Floating{z, ..}=>z,
+            _=>0,
+        };
+        pass.place(root, rect, None, z, None);
+
+        let mut layout = pass.layout;
+        layout.draw_order = (0..layout.nodes.len()).collect();
+        // The sort is stable, so nodes with the same `z` stay in tree order
+        layout.draw_order.sort_by_key(|i|layout.nodes[*i].z);
+
+        return layout;
+    }
+
+    /// The topmost node under `point`. Children are above their parents, and higher `z` values
+    /// are above lower ones.
+    pub fn hit_test(&self, point: Point2)->Option<usize> {
+        self.draw_order.iter()
+            .rev()
+            .copied()
+            .find(|i|self.nodes[*i].hit(point))
+    }
+
+    /// The focusable nodes in Tab order. See [`Container::tab_index`].
+    pub fn focus_order(&self)->Vec<usize> {
+        let mut order = self.nodes.iter()
+            .enumerate()
+            .filter(|(_, node)|node.focusable && node.tab_index.is_none_or(|i|i >= 0))
+            .map(|(i, node)|(i, node.tab_index.filter(|i|*i > 0)))
+            .collect::<Vec<_>>();
+        // Positive indices first, then everything else in tree order. The sort is stable.
+        order.sort_by_key(|(_, tab_index)|match tab_index {
+            Some(i)=>(0, *i),
+            None=>(1, 0),
+        });
+
+        return order.into_iter()
+            .map(|(i, _)|i)
+            .collect();
+    }
+
+    /// The container that should be focused after `current` when pressing Tab (or Shift-Tab if
+    /// `backwards`). Wraps around at the ends.
+    pub fn next_focus(&self, current: Option<Uuid>, backwards: bool)->Option<Uuid> {
+        let order = self.focus_order();
+        if order.is_empty() {
+            return None;
+        }
+
+        let position = current.and_then(|id|order.iter().position(|i|self.nodes[*i].id == id));
+        let next = match (position, backwards) {
+            (Some(p), false)=>(p + 1) % order.len(),
+            (Some(p), true)=>(p + order.len() - 1) % order.len(),
+            (None, false)=>0,
+            (None, true)=>order.len() - 1,
+        };
+
+        return Some(self.nodes[order[next]].id);
+    }
+
+    /// The closest focusable node, starting with `index`
+    pub fn focusable_ancestor(&self, index: usize)->Option<usize> {
+        self.ancestors(index)
+            .find(|i|self.nodes[*i].focusable)
+    }
+
+    /// Apply the [`FOCUS_RING_CLASS`] to the style of the focused container
+    pub fn apply_focus_ring(&mut self, focused: Option<Uuid>, classes: &ClassRegistry) {
+        let Some(ring) = classes.get(FOCUS_RING_CLASS) else {return};
+        let Some(index) = focused.and_then(|id|self.index.get(&id).copied()) else {return};
+        self.nodes[index].style.apply(ring);
+    }
+
+    /// The node and all of its parents, starting with the node
+    pub fn ancestors(&self, index: usize)->impl Iterator<Item = usize> + '_ {
+        std::iter::successors(Some(index), |i|self.nodes[*i].parent)
+    }
+
+    /// Node indices sorted from bottom to top. Nodes with the same `z` stay in tree order.
+    #[inline]
+    pub fn draw_order(&self)->&[usize] {
+        &self.draw_order
+    }
+
+    #[inline]
+    pub fn get(&self, id: Uuid)->Option<&LayoutNode> {
+        self.index.get(&id).map(|i|&self.nodes[*i])
+    }
+
+    #[inline]
+    pub fn rect(&self, id: Uuid)->Option<Rect> {
+        self.get(id).map(|node|node.rect)
+    }
+
+    /// The rectangle of a cell in the grid container with the given ID
+    pub fn cell_rect(&self, grid: Uuid, cell: GridCell)->Option<Rect> {
+        self.grids.get(&grid)?.cell_rect(cell)
+    }
+
+    #[inline]
+    pub fn scroll_extent(&self, id: Uuid)->Option<ScrollExtent> {
+        self.scrolls.get(&id).copied()
+    }
+}
+
+/// What children inherit from the container they are placed in
+#[derive(Copy, Clone)]
+struct ParentInfo {
+    index: usize,
+    z: i32,
+    clip: Option<Rect>,
+}
+
+struct LayoutPass<'a> {
+    layout: Layout,
+    classes: &'a ClassRegistry,
+}
+impl LayoutPass<'_> {
+    fn place(&mut self, container: &Container, rect: Rect, parent: Option<usize>, z: i32, clip: Option<Rect>) {
+        let style = self.classes.resolve(container);
+        let rect = rect.shrink(style.margin);
+        let content = rect.shrink(style.padding);
+
+        let index = self.layout.nodes.len();
+        self.layout.nodes.push(LayoutNode {
+            id: container.id,
+            rect,
+            parent,
+            z,
+            clip,
+            focusable: container.focusable,
+            tab_index: container.tab_index,
+            style,
+        });
+        self.layout.index.insert(container.id, index);
+
+        let overflow = container.overflow;
+        let clip = match overflow.clips() {
+            true=>Some(clip.map_or(rect, |clip|clip.intersect(&rect))),
+            false=>clip,
+        };
+
+        // Children are laid out in the content area moved by the scroll offset
+        let scroll = Vec2::new(
+            if overflow.scrolls_x() {container.scroll.x} else {0.0},
+            if overflow.scrolls_y() {container.scroll.y} else {0.0},
+        );
+        let scrolled = Rect {
+            x: content.x - scroll.x,
+            y: content.y - scroll.y,
+            ..content
+        };
+
+        let parent = ParentInfo {index, z, clip};
+        match &container.layout {
+            LayoutMode::Horizontal=>self.place_flow(container, scrolled, parent, true),
+            LayoutMode::Vertical=>self.place_flow(container, scrolled, parent, false),
+            LayoutMode::Grid(grid)=>self.place_grid(container, grid, scrolled, parent),
+            LayoutMode::Dock=>self.place_dock(container, scrolled, parent),
+        }
+
+        if overflow.scrolls_x() || overflow.scrolls_y() {
+            let mut size = Vec2::new(content.width, content.height);
+            for child in self.layout.nodes[index + 1..].iter().filter(|n|n.parent == Some(index)) {
+                size.x = size.x.max(child.rect.right() - scrolled.x);
+                size.y = size.y.max(child.rect.bottom() - scrolled.y);
+            }
+            self.layout.scrolls.insert(container.id, ScrollExtent {
+                viewport: content,
+                content: size,
+            });
+        }
+
+        for child in container.children.iter() {
+            let Placement::Floating{x, y, z} = child.placement else {continue};
+            let rect = Rect {
+                x,
+                y,
+                width: resolve_cross(child.width, content.width),
+                height: resolve_cross(child.height, content.height),
+            };
+            // Floating containers are overlays, so they aren't clipped by their parents
+            self.place(child, rect, Some(index), z, None);
+        }
+    }
+
+    /// Place the children one after another along the main axis.
+    fn place_flow(&mut self, container: &Container, content: Rect, parent: ParentInfo, horizontal: bool) {
+        let (main_avail, cross_avail) = if horizontal {
+            (content.width, content.height)
+        } else {
+            (content.height, content.width)
+        };
+
+        let children = container.children.iter()
+            .filter(|c|!c.is_floating());
+        let main_sizes = children.clone()
+            .map(|c|if horizontal {c.width} else {c.height});
+        let tracks = resolve_tracks(main_sizes, main_avail, container.gap as f32);
+
+        for (child, track) in children.zip(tracks) {
+            let rect = if horizontal {
+                let cross = resolve_cross(child.height, cross_avail);
+                Rect::new(content.x + track.start, content.y, track.size, cross)
+            } else {
+                let cross = resolve_cross(child.width, cross_avail);
+                Rect::new(content.x, content.y + track.start, cross, track.size)
+            };
+            self.place(child, rect, Some(parent.index), parent.z, parent.clip);
+        }
+    }
+
+    /// Resolve the grid tracks, then place each child in its cell. Children without a cell take
+    /// the next cell (in row-major order) that isn't covered by an explicitly placed child.
+    fn place_grid(&mut self, container: &Container, grid: &Grid, content: Rect, parent: ParentInfo) {
+        let gap = container.gap as f32;
+        let tracks = GridTracks {
+            content,
+            rows: resolve_tracks(grid.rows.iter().copied(), content.height, gap),
+            columns: resolve_tracks(grid.columns.iter().copied(), content.width, gap),
+        };
+
+        let row_count = tracks.rows.len();
+        let column_count = tracks.columns.len();
+        let children = container.children.iter()
+            .filter(|c|!c.is_floating());
+        let mut occupied = vec![false; row_count * column_count];
+        for cell in children.clone().filter_map(|c|c.cell) {
+            let rows = cell.row..(cell.row + cell.row_span.max(1)).min(row_count);
+            let columns = cell.column..(cell.column + cell.column_span.max(1)).min(column_count);
+            for row in rows {
+                for column in columns.clone() {
+                    occupied[row * column_count + column] = true;
+                }
+            }
+        }
+
+        let mut next_free = 0;
+        for child in children {
+            let cell = match child.cell {
+                Some(cell)=>Some(cell),
+                None=>{
+                    while next_free < occupied.len() && occupied[next_free] {
+                        next_free += 1;
+                    }
+                    let cell = (next_free < occupied.len())
+                        .then(||GridCell::new(next_free / column_count, next_free % column_count));
+                    next_free += 1;
+                    cell
+                },
+            };
+
+            let rect = match cell.and_then(|cell|tracks.cell_rect(cell)) {
+                Some(area)=>Rect {
+                    x: area.x,
+                    y: area.y,
+                    width: resolve_cross(child.width, area.width),
+                    height: resolve_cross(child.height, area.height),
+                },
+                None=>{
+                    warn!("Grid child `{}` does not fit in the grid", child.id);
+                    Rect::new(content.x, content.y, 0.0, 0.0)
+                },
+            };
+            self.place(child, rect, Some(parent.index), parent.z, parent.clip);
+        }
+
+        self.layout.grids.insert(container.id, tracks);
+    }
+
+    /// Dock each child to an edge of the space that is still free. Children that aren't docked
+    /// are treated as [`DockEdge::Center`].
+    fn place_dock(&mut self, container: &Container, content: Rect, parent: ParentInfo) {
+        let mut free = content;
+
+        for child in container.children.iter().filter(|c|!c.is_floating()) {
+            let edge = match child.placement {
+                Placement::Dock(edge)=>edge,
+                _=>DockEdge::Center,
+            };
+
+            let rect = match edge {
+                DockEdge::Top|DockEdge::Bottom=>{
+                    let height = resolve_cross(child.height, free.height).min(free.height);
+                    let y = if edge == DockEdge::Top {free.y} else {free.bottom() - height};
+                    if edge == DockEdge::Top {
+                        free.y += height;
+                    }
+                    free.height -= height;
+                    Rect::new(free.x, y, free.width, height)
+                },
+                DockEdge::Left|DockEdge::Right=>{
+                    let width = resolve_cross(child.width, free.width).min(free.width);
+                    let x = if edge == DockEdge::Left {free.x} else {free.right() - width};
+                    if edge == DockEdge::Left {
+                        free.x += width;
+                    }
+                    free.width -= width;
+                    Rect::new(x, free.y, width, free.height)
+                },
+                DockEdge::Center=>{
+                    let rect = free;
+                    free.width = 0.0;
+                    free.height = 0.0;
+                    rect
+                },
+            };
+            self.place(child, rect, Some(parent.index), parent.z, parent.clip);
+        }
+    }
+}
+
+
+/// Split `avail` pixels between tracks of the given sizes. Pixel tracks get their exact size
+/// and the free space is split between the weighted tracks.
+fn resolve_tracks(sizes: impl Iterator<Item = Size> + Clone, avail: f32, gap: f32)->Vec<Track> {
+    let mut count = 0;
+    let mut fixed = 0.0;
+    let mut total_weight = 0;
+    for size in sizes.clone() {
+        count += 1;
+        match size {
+            Size::Pixels(px)=>fixed += px as f32,
+            Size::Weight(w)=>total_weight += w,
+            Size::Fill=>total_weight += 1,
+        }
+    }
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let free = (avail - gap * (count - 1) as f32 - fixed).max(0.0);
+
+    let mut cursor = 0.0;
+    let mut tracks = Vec::with_capacity(count);
+    for size in sizes {
+        let size = match size {
+            Size::Pixels(px)=>px as f32,
+            Size::Weight(w)=>weighted(free, w, total_weight),
+            Size::Fill=>weighted(free, 1, total_weight),
+        };
+        tracks.push(Track {start: cursor, size});
+        cursor += size + gap;
+    }
+
+    return tracks;
+}
+
+fn weighted(free: f32, weight: u32, total: u32)->f32 {
+    if total == 0 {
+        return 0.0;
+    }
+    return free * weight as f32 / total as f32;
+}
+
+/// Resolve a size that doesn't share space with its siblings
+fn resolve_cross(size: Size, avail: f32)->f32 {
+    match size {
+        Size::Pixels(px)=>px as f32,
+        Size::Weight(_)|Size::Fill=>avail,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::{
+        container::Overflow,
+        button::Button,
+        Edges,
+    };
+
+    const VIEWPORT: Rect = Rect::new(0.0, 0.0, 300.0, 100.0);
+
+    fn assert_rect(actual: Rect, expected: Rect) {
+        let close = (actual.x - expected.x).abs() < 1e-3 &&
+            (actual.y - expected.y).abs() < 1e-3 &&
+            (actual.width - expected.width).abs() < 1e-3 &&
+            (actual.height - expected.height).abs() < 1e-3;
+        assert!(close, "expected {expected:?}, got {actual:?}");
+    }
+
+    /// The rects of the root's children. Nodes are in tree order, so these come right after the
+    /// root when the children have no children of their own.
+    fn child_rects(root: &Container)->Vec<Rect> {
+        let layout = Layout::compute(root, VIEWPORT);
+        return layout.nodes[1..].iter()
+            .map(|node|node.rect)
+            .collect();
+    }
+
+    #[test]
+    fn flow_main_axis() {
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_child(Container::new(Size::Pixels(50), Size::Fill))
+            .with_child(Container::new(Size::Weight(1), Size::Fill))
+            .with_child(Container::new(Size::Weight(2), Size::Fill));
+        let rects = child_rects(&root);
+
+        assert_rect(rects[0], Rect::new(0.0, 0.0, 50.0, 100.0));
+        assert_rect(rects[1], Rect::new(50.0, 0.0, 250.0 / 3.0, 100.0));
+        assert_rect(rects[2], Rect::new(50.0 + 250.0 / 3.0, 0.0, 500.0 / 3.0, 100.0));
+    }
+
+    #[test]
+    fn flow_gap() {
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_layout(LayoutMode::Vertical)
+            .with_gap(10)
+            .with_child(Container::new(Size::Fill, Size::Pixels(20)))
+            .with_child(Container::new(Size::Fill, Size::Fill))
+            .with_child(Container::new(Size::Fill, Size::Fill));
+        let rects = child_rects(&root);
+
+        // 100 - 20 - 2 gaps = 60 free pixels
+        assert_rect(rects[0], Rect::new(0.0, 0.0, 300.0, 20.0));
+        assert_rect(rects[1], Rect::new(0.0, 30.0, 300.0, 30.0));
+        assert_rect(rects[2], Rect::new(0.0, 70.0, 300.0, 30.0));
+    }
+
+    #[test]
+    fn flow_cross_axis() {
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_padding(Edges::all(10))
+            .with_child(Container::new(Size::Pixels(20), Size::Pixels(30)))
+            .with_child(Container::new(Size::Pixels(20), Size::Fill))
+            .with_child(Container::new(Size::Pixels(20), Size::Weight(3)));
+        let rects = child_rects(&root);
+
+        // Children start at the top of the content area. Fixed sizes are kept, and everything
+        // else stretches to the content height.
+        assert_rect(rects[0], Rect::new(10.0, 10.0, 20.0, 30.0));
+        assert_rect(rects[1], Rect::new(30.0, 10.0, 20.0, 80.0));
+        assert_rect(rects[2], Rect::new(50.0, 10.0, 20.0, 80.0));
+    }
+
+    #[test]
+    fn flow_clamps_overflow() {
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_gap(10)
+            .with_child(Container::new(Size::Pixels(200), Size::Fill))
+            .with_child(Container::new(Size::Pixels(100), Size::Fill))
+            .with_child(Container::new(Size::Fill, Size::Fill));
+        let rects = child_rects(&root);
+
+        // Pixel tracks keep their size past the end, and the free space never goes negative
+        assert_rect(rects[0], Rect::new(0.0, 0.0, 200.0, 100.0));
+        assert_rect(rects[1], Rect::new(210.0, 0.0, 100.0, 100.0));
+        assert_rect(rects[2], Rect::new(320.0, 0.0, 0.0, 100.0));
+    }
+
+    #[test]
+    fn padding_clamps_to_zero() {
+        let root = Container::new(Size::Pixels(30), Size::Pixels(30))
+            .with_padding(Edges::all(20))
+            .with_child(Container::new(Size::Fill, Size::Fill));
+        let rects = child_rects(&root);
+
+        assert_rect(rects[0], Rect::new(20.0, 20.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn grid_tracks() {
+        let sizes = [Size::Pixels(100), Size::Weight(1), Size::Weight(3), Size::Fill];
+        let tracks = resolve_tracks(sizes.into_iter(), 420.0, 10.0);
+
+        // 420 - 100 - 3 gaps = 290 free pixels split 1:3:1
+        let expected = [(0.0, 100.0), (110.0, 58.0), (178.0, 174.0), (362.0, 58.0)];
+        assert_eq!(tracks.len(), expected.len());
+        for (track, (start, size)) in tracks.iter().zip(expected) {
+            assert!((track.start - start).abs() < 1e-3, "{track:?}");
+            assert!((track.size - size).abs() < 1e-3, "{track:?}");
+        }
+        assert!(resolve_tracks(std::iter::empty(), 100.0, 10.0).is_empty());
+    }
+
+    #[test]
+    fn grid_cell_spans() {
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_layout(LayoutMode::Grid(Grid::new(
+                [Size::Pixels(20), Size::Fill],
+                [Size::Pixels(50), Size::Weight(1), Size::Weight(1)],
+            )))
+            .with_gap(10);
+        let layout = Layout::compute(&root, VIEWPORT);
+        let tracks = &layout.grids[&root.id];
+
+        assert_rect(tracks.cell_rect(GridCell::new(0, 0)).unwrap(), Rect::new(0.0, 0.0, 50.0, 20.0));
+        assert_rect(tracks.cell_rect(GridCell::new(1, 2)).unwrap(), Rect::new(185.0, 30.0, 115.0, 70.0));
+        // Spans cover the gaps between their tracks
+        assert_rect(tracks.cell_rect(GridCell::new(0, 1).span(2, 2)).unwrap(), Rect::new(60.0, 0.0, 240.0, 100.0));
+        // A span of 0 is treated as 1
+        assert_rect(tracks.cell_rect(GridCell::new(1, 0).span(0, 0)).unwrap(), Rect::new(0.0, 30.0, 50.0, 70.0));
+        assert_eq!(tracks.cell_rect(GridCell::new(2, 0)), None);
+        assert_eq!(tracks.cell_rect(GridCell::new(0, 2).span(1, 2)), None);
+    }
+
+    #[test]
+    fn grid_auto_placement() {
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_layout(LayoutMode::Grid(Grid::new(
+                [Size::Fill, Size::Fill],
+                [Size::Fill, Size::Fill],
+            )))
+            .with_child(Container::new(Size::Fill, Size::Fill))
+            .with_child(Container::new(Size::Fill, Size::Fill).with_cell(GridCell::new(0, 1).span(2, 1)))
+            .with_child(Container::new(Size::Fill, Size::Pixels(10)))
+            .with_child(Container::new(Size::Fill, Size::Fill));
+        let rects = child_rects(&root);
+
+        assert_rect(rects[0], Rect::new(0.0, 0.0, 150.0, 50.0));
+        assert_rect(rects[1], Rect::new(150.0, 0.0, 150.0, 100.0));
+        // Skips the cell covered by the span. Fixed sizes are kept inside of the cell.
+        assert_rect(rects[2], Rect::new(0.0, 50.0, 150.0, 10.0));
+        // There are no free cells left
+        assert_rect(rects[3], Rect::new(0.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn dock_edges() {
+        let dock = |edge, width, height|Container::new(Size::Pixels(width), Size::Pixels(height))
+            .with_placement(Placement::Dock(edge));
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_layout(LayoutMode::Dock)
+            .with_child(dock(DockEdge::Top, 0, 20))
+            .with_child(dock(DockEdge::Left, 50, 0))
+            .with_child(dock(DockEdge::Bottom, 0, 10))
+            .with_child(dock(DockEdge::Right, 30, 0))
+            .with_child(Container::new(Size::Fill, Size::Fill));
+        let rects = child_rects(&root);
+
+        // Each edge takes its side of the space the earlier ones left
+        assert_rect(rects[0], Rect::new(0.0, 0.0, 300.0, 20.0));
+        assert_rect(rects[1], Rect::new(0.0, 20.0, 50.0, 80.0));
+        assert_rect(rects[2], Rect::new(50.0, 90.0, 250.0, 10.0));
+        assert_rect(rects[3], Rect::new(270.0, 20.0, 30.0, 70.0));
+        // Children that aren't docked take the rest
+        assert_rect(rects[4], Rect::new(50.0, 20.0, 220.0, 70.0));
+    }
+
+    #[test]
+    fn dock_clamps_to_free_space() {
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_layout(LayoutMode::Dock)
+            .with_child(Container::new(Size::Fill, Size::Pixels(80)).with_placement(Placement::Dock(DockEdge::Bottom)))
+            .with_child(Container::new(Size::Fill, Size::Pixels(80)).with_placement(Placement::Dock(DockEdge::Top)))
+            .with_child(Container::new(Size::Fill, Size::Fill).with_placement(Placement::Dock(DockEdge::Center)))
+            .with_child(Container::new(Size::Fill, Size::Fill));
+        let rects = child_rects(&root);
+
+        assert_rect(rects[0], Rect::new(0.0, 20.0, 300.0, 80.0));
+        assert_rect(rects[1], Rect::new(0.0, 0.0, 300.0, 20.0));
+        assert_rect(rects[2], Rect::new(0.0, 20.0, 300.0, 0.0));
+        // The first center child took all of the space
+        assert_eq!(rects[3].width * rects[3].height, 0.0);
+    }
+
+    #[test]
+    fn floating_placement() {
+        let floating = |x, y, z|Container::new(Size::Pixels(40), Size::Fill)
+            .with_placement(Placement::Floating {x, y, z});
+        let top = floating(5.0, 6.0, 2);
+        let top_id = top.id;
+        let bottom = floating(200.0, 0.0, 1);
+        let bottom_id = bottom.id;
+        let nested = Container::new(Size::Pixels(10), Size::Pixels(10));
+        let nested_id = nested.id;
+        let flow = Container::new(Size::Fill, Size::Fill);
+        let flow_id = flow.id;
+        let parent = Container::new(Size::Pixels(100), Size::Pixels(50))
+            .with_padding(Edges::all(10))
+            .with_overflow(Overflow::Clip)
+            .with_child(top)
+            .with_child(flow)
+            .with_child(bottom.with_child(nested));
+        let sibling = Container::new(Size::Fill, Size::Fill);
+        let sibling_id = sibling.id;
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_padding(Edges::all(20))
+            .with_child(parent)
+            .with_child(sibling);
+        let layout = Layout::compute(&root, VIEWPORT);
+
+        // Positions are in window pixels, and sizes fill the parent's content area
+        assert_rect(layout.rect(top_id).unwrap(), Rect::new(5.0, 6.0, 40.0, 30.0));
+        assert_rect(layout.rect(bottom_id).unwrap(), Rect::new(200.0, 0.0, 40.0, 30.0));
+        // Floating children don't take space from the layout
+        assert_rect(layout.rect(flow_id).unwrap(), Rect::new(30.0, 30.0, 80.0, 30.0));
+        assert_rect(layout.rect(sibling_id).unwrap(), Rect::new(120.0, 20.0, 160.0, 60.0));
+
+        // They aren't clipped by their parent, and their children inherit their `z`
+        assert_eq!(layout.get(top_id).unwrap().clip, None);
+        assert_eq!(layout.get(bottom_id).unwrap().clip, None);
+        assert_eq!(layout.get(nested_id).unwrap().z, 1);
+
+        // Drawn above everything else, ordered by `z`
+        let order = layout.draw_order().iter()
+            .map(|i|layout.nodes[*i].id)
+            .collect::<Vec<_>>();
+        assert_eq!(order[order.len() - 3..], [bottom_id, nested_id, top_id]);
+    }
+
+    #[test]
+    fn focus_order() {
+        let mut disabled = Button::new();
+        disabled.set_disabled(true);
+        let children = [
+            Container::new(Size::Fill, Size::Fill).with_focusable(true),
+            Container::new(Size::Fill, Size::Fill).with_focusable(true).with_tab_index(2),
+            Container::new(Size::Fill, Size::Fill),
+            Container::new(Size::Fill, Size::Fill).with_focusable(true).with_tab_index(-1),
+            disabled.container(Size::Fill, Size::Fill),
+            Container::new(Size::Fill, Size::Fill).with_focusable(true).with_tab_index(1),
+            Container::new(Size::Fill, Size::Fill).with_focusable(true).with_tab_index(0),
+        ];
+        let ids = children.iter().map(|c|c.id).collect::<Vec<_>>();
+        let mut root = Container::new(Size::Fill, Size::Fill);
+        for child in children {
+            root.push(child);
+        }
+        let layout = Layout::compute(&root, VIEWPORT);
+
+        // Positive indices first, then the rest in tree order. Negative indices, unfocusable and
+        // disabled containers are skipped.
+        let order = layout.focus_order().into_iter()
+            .map(|i|layout.nodes[i].id)
+            .collect::<Vec<_>>();
+        let expected = [ids[5], ids[1], ids[0], ids[6]];
+        assert_eq!(order, expected);
+
+        // Tab goes forwards and wraps around
+        assert_eq!(layout.next_focus(None, false), Some(expected[0]));
+        for i in 0..expected.len() {
+            assert_eq!(layout.next_focus(Some(expected[i]), false), Some(expected[(i + 1) % expected.len()]));
+        }
+
+        // Shift-Tab goes backwards and wraps around
+        assert_eq!(layout.next_focus(None, true), Some(expected[3]));
+        for i in 0..expected.len() {
+            assert_eq!(layout.next_focus(Some(expected[i]), true), Some(expected[(i + 3) % expected.len()]));
+        }
+
+        // Containers that were focused by clicking start the order over
+        assert_eq!(layout.next_focus(Some(ids[3]), false), Some(expected[0]));
+        assert_eq!(layout.next_focus(Some(ids[3]), true), Some(expected[3]));
+    }
+
+    #[test]
+    fn no_focusable_nodes() {
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_child(Container::new(Size::Fill, Size::Fill));
+        let layout = Layout::compute(&root, VIEWPORT);
+        assert!(layout.focus_order().is_empty());
+        assert_eq!(layout.next_focus(None, false), None);
+        assert_eq!(layout.next_focus(Some(root.id), true), None);
+    }
+
+    /// The ID of the node hit at `(x, y)`
+    fn hit_id(layout: &Layout, x: f32, y: f32)->Option<Uuid> {
+        layout.hit_test(Point2::new(x, y)).map(|i|layout.nodes[i].id)
+    }
+
+    #[test]
+    fn hit_children_above_parents() {
+        let child = Container::new(Size::Pixels(50), Size::Pixels(50));
+        let child_id = child.id;
+        let root = Container::new(Size::Fill, Size::Fill).with_child(child);
+        let layout = Layout::compute(&root, VIEWPORT);
+
+        assert_eq!(hit_id(&layout, 10.0, 10.0), Some(child_id));
+        assert_eq!(hit_id(&layout, 100.0, 10.0), Some(root.id));
+        assert_eq!(hit_id(&layout, 400.0, 10.0), None);
+    }
+
+    #[test]
+    fn hit_floating_over_sibling() {
+        // The overlay belongs to the first child but reaches over the second one, which comes
+        // later in tree order
+        let overlay = Container::new(Size::Pixels(100), Size::Pixels(50))
+            .with_placement(Placement::Floating {x: 120.0, y: 10.0, z: 1});
+        let overlay_id = overlay.id;
+        let first = Container::new(Size::Fill, Size::Fill).with_child(overlay);
+        let second = Container::new(Size::Fill, Size::Fill);
+        let second_id = second.id;
+        let root = Container::new(Size::Fill, Size::Fill)
+            .with_child(first)
+            .with_child(second);
+        let layout = Layout::compute(&root, VIEWPORT);
+
+        assert_eq!(hit_id(&layout, 160.0, 20.0), Some(overlay_id));
+        assert_eq!(hit_id(&layout, 160.0, 80.0), Some(second_id));
+        assert_eq!(layout.draw_order().last(), layout.index.get(&overlay_id));
+    }
+
+    #[test]
+    fn hit_respects_clip() {
+        let inner = Container::new(Size::Pixels(200), Size::Pixels(40));
+        let inner_id = inner.id;
+        let overlay = Container::new(Size::Pixels(20), Size::Pixels(20))
+            .with_placement(Placement::Floating {x: 150.0, y: 60.0, z: 0});
+        let overlay_id = overlay.id;
+        let clipped = Container::new(Size::Pixels(100), Size::Pixels(50))
+            .with_overflow(Overflow::Clip)
+            .with_child(inner)
+            .with_child(overlay);
+        let clipped_id = clipped.id;
+        let root = Container::new(Size::Fill, Size::Fill).with_child(clipped);
+        let layout = Layout::compute(&root, VIEWPORT);
+
+        assert_eq!(hit_id(&layout, 50.0, 10.0), Some(inner_id));
+        // Inside of the child's rectangle, but outside of its parent
+        assert!(layout.get(inner_id).unwrap().rect.contains(Point2::new(150.0, 10.0)));
+        assert_eq!(hit_id(&layout, 150.0, 10.0), Some(root.id));
+        assert_eq!(hit_id(&layout, 50.0, 45.0), Some(clipped_id));
+        // Floating children aren't clipped
+        assert_eq!(hit_id(&layout, 155.0, 65.0), Some(overlay_id));
+    }
+}
@@ -2,13 +2,230 @@
 //! The system is immediate, so it does the layout each frame.
 
 
+use serde::{
+    Serialize,
+    Deserialize,
+};
+use crate::{
+    render::ClipRect,
+    math::*,
+    Color,
+};
+
+
 pub mod text;
+pub mod glyph_cache;
 pub mod button;
+pub mod input;
 pub mod container;
+pub mod scroll;
+pub mod layout;
+pub mod style;
+pub mod event;
+pub mod stylesheet;
 
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Size {
+    /// A share of the free space left after all [`Size::Pixels`] siblings are placed. Only
+    /// meaningful along the main axis; on the cross axis this acts like [`Size::Fill`].
     Weight(u32),
     Pixels(u32),
+    /// Take up all the available space. Along the main axis this is the same as `Weight(1)`.
     Fill,
 }
+
+/// Space around the four edges of a rectangle in pixels
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Edges {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+impl Edges {
+    pub const ZERO: Self = Edges::all(0);
+
+    pub const fn all(px: u32)->Self {
+        Edges {
+            top: px,
+            right: px,
+            bottom: px,
+            left: px,
+        }
+    }
+
+    pub const fn symmetric(vertical: u32, horizontal: u32)->Self {
+        Edges {
+            top: vertical,
+            right: horizontal,
+            bottom: vertical,
+            left: horizontal,
+        }
+    }
+
+    #[inline]
+    pub const fn horizontal(&self)->u32 {
+        self.left + self.right
+    }
+
+    #[inline]
+    pub const fn vertical(&self)->u32 {
+        self.top + self.bottom
+    }
+}
+
+/// A radius for each corner of a rectangle in pixels. In a stylesheet this can also be a single
+/// number for all four corners.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(from = "CornersDef")]
+pub struct Corners {
+    pub top_left: f32,
+    pub top_right: f32,
+    pub bottom_right: f32,
+    pub bottom_left: f32,
+}
+impl Corners {
+    pub const ZERO: Self = Corners::all(0.0);
+
+    pub const fn all(radius: f32)->Self {
+        Corners {
+            top_left: radius,
+            top_right: radius,
+            bottom_right: radius,
+            bottom_left: radius,
+        }
+    }
+
+    /// Top left, top right, bottom right, then bottom left
+    #[inline]
+    pub const fn to_array(&self)->[f32; 4] {
+        [self.top_left, self.top_right, self.bottom_right, self.bottom_left]
+    }
+}
+
+/// The forms [`Corners`] can be written in
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CornersDef {
+    All(f32),
+    Each {
+        #[serde(default)]
+        top_left: f32,
+        #[serde(default)]
+        top_right: f32,
+        #[serde(default)]
+        bottom_right: f32,
+        #[serde(default)]
+        bottom_left: f32,
+    },
+}
+impl From<CornersDef> for Corners {
+    fn from(def: CornersDef)->Self {
+        match def {
+            CornersDef::All(radius)=>Corners::all(radius),
+            CornersDef::Each{top_left, top_right, bottom_right, bottom_left}=>Corners {
+                top_left,
+                top_right,
+                bottom_right,
+                bottom_left,
+            },
+        }
+    }
+}
+
+/// A drop shadow behind an element, like a CSS box shadow. Sizes are in pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Shadow {
+    pub color: Color,
+    pub offset_x: f32,
+    pub offset_y: f32,
+    /// How far the edge of the shadow fades out over
+    pub blur: f32,
+    /// How much bigger than the element the shadow is on each side
+    pub spread: f32,
+}
+impl Default for Shadow {
+    fn default()->Self {
+        Shadow {
+            color: Color(0.0, 0.0, 0.0, 0.5),
+            offset_x: 0.0,
+            offset_y: 2.0,
+            blur: 8.0,
+            spread: 0.0,
+        }
+    }
+}
+
+/// An axis aligned rectangle in window pixels. The origin is the top left of the window.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+impl Rect {
+    pub const fn new(x: f32, y: f32, width: f32, height: f32)->Self {
+        Rect {x, y, width, height}
+    }
+
+    #[inline]
+    pub fn right(&self)->f32 {
+        self.x + self.width
+    }
+
+    #[inline]
+    pub fn bottom(&self)->f32 {
+        self.y + self.height
+    }
+
+    #[inline]
+    pub fn contains(&self, point: Point2)->bool {
+        point.x >= self.x &&
+            point.y >= self.y &&
+            point.x < self.right() &&
+            point.y < self.bottom()
+    }
+
+    /// Shrink the rectangle by the given edges. The size never goes below zero.
+    pub fn shrink(&self, edges: Edges)->Self {
+        let x = self.x + edges.left as f32;
+        let y = self.y + edges.top as f32;
+        return Rect {
+            x,
+            y,
+            width: (self.width - edges.horizontal() as f32).max(0.0),
+            height: (self.height - edges.vertical() as f32).max(0.0),
+        };
+    }
+
+    /// The overlapping area of both rectangles. Returns a zero-sized rectangle if they don't
+    /// overlap.
+    pub fn intersect(&self, other: &Self)->Self {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        return Rect {
+            x,
+            y,
+            width: (right - x).max(0.0),
+            height: (bottom - y).max(0.0),
+        };
+    }
+
+    /// The smallest whole pixel clip rectangle that covers this one
+    pub fn to_clip(&self)->ClipRect {
+        let x = self.x.floor();
+        let y = self.y.floor();
+        return ClipRect {
+            x: x as i32,
+            y: y as i32,
+            width: (self.right().ceil() - x).max(0.0) as u32,
+            height: (self.bottom().ceil() - y).max(0.0) as u32,
+        };
+    }
+}
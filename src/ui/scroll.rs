@@ -0,0 +1,548 @@
+//! Scrolling containers. A [`ScrollView`] keeps the scroll offset between frames, turns wheel,
+//! touch and scrollbar events into scrolling, and keeps scrolling after a fling with
+//! [`KineticScroll`].
+
+
+use winit::event::{
+    MouseButton,
+    MouseScrollDelta,
+    TouchPhase,
+};
+use anyhow::Result;
+use crate::{
+    render::{
+        RenderFrame,
+        RoundedRect,
+    },
+    math::*,
+    Uuid,
+    Color,
+};
+use super::{
+    container::{
+        Container,
+        Overflow,
+    },
+    event::{
+        UiEvent,
+        UiEventKind,
+    },
+    layout::{
+        Layout,
+        ScrollExtent,
+    },
+    Size,
+    Rect,
+};
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// Where to draw a scrollbar, in window pixels
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Scrollbar {
+    pub axis: Axis,
+    pub track: Rect,
+    pub thumb: Rect,
+    /// The pointer is over the thumb
+    pub hovered: bool,
+    /// The thumb is being dragged
+    pub dragging: bool,
+}
+
+/// Scrolling that slows down over time after a fling. This only depends on the time steps it is
+/// given, so the same steps always give the same motion.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct KineticScroll {
+    /// Pixels per second
+    pub velocity: Vec2,
+    /// How quickly the velocity decays. The velocity is multiplied by `e^(-friction * dt)` each
+    /// step.
+    pub friction: f32,
+    /// Scrolling stops below this speed in pixels per second
+    pub min_velocity: f32,
+}
+impl KineticScroll {
+    pub fn new(friction: f32, min_velocity: f32)->Self {
+        KineticScroll {
+            velocity: Vec2::zero(),
+            friction,
+            min_velocity,
+        }
+    }
+
+    #[inline]
+    pub fn is_moving(&self)->bool {
+        self.velocity != Vec2::zero()
+    }
+
+    #[inline]
+    pub fn stop(&mut self) {
+        self.velocity = Vec2::zero();
+    }
+
+    /// Advance the simulation by `dt` seconds and return how far it moved. The distance is the
+    /// exact integral of the decaying velocity, so it doesn't depend on how time is split into
+    /// steps.
+    pub fn step(&mut self, dt: f32)->Vec2 {
+        if self.velocity.mag() < self.min_velocity || dt <= 0.0 {
+            self.velocity = Vec2::zero();
+            return Vec2::zero();
+        }
+
+        let decay = (-self.friction * dt).exp();
+        let distance = if self.friction > 0.0 {
+            self.velocity * ((1.0 - decay) / self.friction)
+        } else {
+            self.velocity * dt
+        };
+        self.velocity *= decay;
+
+        return distance;
+    }
+}
+impl Default for KineticScroll {
+    fn default()->Self {
+        Self::new(4.0, 10.0)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Drag {
+    /// Dragging a scrollbar thumb. `grab` is where the thumb was grabbed, from its start.
+    Thumb {
+        axis: Axis,
+        grab: f32,
+    },
+    Touch {
+        id: u64,
+        last: Point2,
+    },
+}
+
+/// The scroll state of a scrolling container. Keep this between frames, build the container with
+/// [`ScrollView::container`], and call [`ScrollView::update`] after each layout.
+///
+/// Events reach the scroll view by bubbling up from its children, so give it every event sent to
+/// its container and stop the propagation when [`ScrollView::handle_event`] returns `true`. This
+/// lets nested scroll views pass on scrolling they can't do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrollView {
+    pub id: Uuid,
+    /// Which axes scroll. [`Overflow::Visible`] and [`Overflow::Clip`] never scroll.
+    pub overflow: Overflow,
+    /// How far the content is scrolled in pixels
+    pub offset: Vec2,
+    /// The scrollable area from the last layout
+    pub extent: Option<ScrollExtent>,
+    pub kinetic: KineticScroll,
+    /// Pixels scrolled for each line of a mouse wheel
+    pub line_size: f32,
+    pub scrollbar_width: f32,
+    /// The shortest a scrollbar thumb can be
+    pub min_thumb: f32,
+    pub track_color: Color,
+    pub thumb_color: Color,
+    /// The thumb color while it is hovered or dragged
+    pub thumb_active_color: Color,
+    drag: Option<Drag>,
+    hovered: Option<Axis>,
+    /// A touch or touchpad gesture is in progress. The fling velocity is measured while this is
+    /// set.
+    tracking: bool,
+    /// Distance scrolled by the gesture since the last update
+    tracked: Vec2,
+}
+impl ScrollView {
+    pub fn new(overflow: Overflow)->Self {
+        ScrollView {
+            id: crate::new_uuid(),
+            overflow,
+            offset: Vec2::zero(),
+            extent: None,
+            kinetic: KineticScroll::default(),
+            line_size: 40.0,
+            scrollbar_width: 8.0,
+            min_thumb: 16.0,
+            track_color: Color(0.0, 0.0, 0.0, 0.1),
+            thumb_color: Color(0.0, 0.0, 0.0, 0.4),
+            thumb_active_color: Color(0.0, 0.0, 0.0, 0.6),
+            drag: None,
+            hovered: None,
+            tracking: false,
+            tracked: Vec2::zero(),
+        }
+    }
+
+    /// A container that scrolls by the current offset. Add the content as children.
+    pub fn container(&self, width: Size, height: Size)->Container {
+        Container::new(width, height)
+            .with_id(self.id)
+            .with_overflow(self.overflow)
+            .with_scroll(self.offset)
+    }
+
+    /// The largest offset on each axis. Axes that don't scroll are always zero.
+    pub fn max_offset(&self)->Vec2 {
+        let max = self.extent
+            .map(|extent|extent.max_offset())
+            .unwrap_or(Vec2::zero());
+        return Vec2::new(
+            if self.overflow.scrolls_x() {max.x} else {0.0},
+            if self.overflow.scrolls_y() {max.y} else {0.0},
+        );
+    }
+
+    /// Scroll to `offset`, clamped to the scrollable area. Returns `true` if the offset changed.
+    pub fn scroll_to(&mut self, offset: Vec2)->bool {
+        let max = self.max_offset();
+        let clamped = Vec2::new(offset.x.clamp(0.0, max.x), offset.y.clamp(0.0, max.y));
+
+        // Hitting an edge stops the kinetic scrolling on that axis
+        if clamped.x != offset.x {
+            self.kinetic.velocity.x = 0.0;
+        }
+        if clamped.y != offset.y {
+            self.kinetic.velocity.y = 0.0;
+        }
+
+        let changed = clamped != self.offset;
+        self.offset = clamped;
+        return changed;
+    }
+
+    #[inline]
+    pub fn scroll_by(&mut self, delta: Vec2)->bool {
+        self.scroll_to(self.offset + delta)
+    }
+
+    /// Scroll as little as possible so that `rect` (in window pixels) can be seen
+    pub fn scroll_into_view(&mut self, rect: Rect)->bool {
+        let Some(extent) = self.extent else {return false};
+        let viewport = extent.viewport;
+        let mut delta = Vec2::zero();
+        if rect.x < viewport.x {
+            delta.x = rect.x - viewport.x;
+        } else if rect.right() > viewport.right() {
+            delta.x = (rect.right() - viewport.right()).min(rect.x - viewport.x);
+        }
+        if rect.y < viewport.y {
+            delta.y = rect.y - viewport.y;
+        } else if rect.bottom() > viewport.bottom() {
+            delta.y = (rect.bottom() - viewport.bottom()).min(rect.y - viewport.y);
+        }
+
+        return self.scroll_by(delta);
+    }
+
+    /// Take the scrollable area from `layout` and advance the kinetic scrolling by `dt` seconds.
+    /// Returns `true` while the view is still moving, so the window should keep redrawing.
+    pub fn update(&mut self, layout: &Layout, dt: f32)->bool {
+        self.extent = layout.scroll_extent(self.id);
+
+        if self.tracking {
+            // Measure the gesture speed, smoothed a little so one uneven frame doesn't decide the
+            // fling
+            if dt > 0.0 {
+                let measured = self.tracked / dt;
+                self.kinetic.velocity = self.kinetic.velocity * 0.2 + measured * 0.8;
+            }
+            self.tracked = Vec2::zero();
+            self.scroll_by(Vec2::zero());
+            return false;
+        }
+
+        let distance = self.kinetic.step(dt);
+        self.scroll_by(distance);
+
+        return self.kinetic.is_moving();
+    }
+
+    /// The scrollbars that should be drawn. There is only a scrollbar for an axis if it scrolls
+    /// and the content doesn't fit.
+    pub fn scrollbars(&self)->Vec<Scrollbar> {
+        let Some(extent) = self.extent else {return Vec::new()};
+        let max = self.max_offset();
+        let viewport = extent.viewport;
+        let width = self.scrollbar_width;
+        let horizontal = max.x > 0.0;
+        let vertical = max.y > 0.0;
+
+        let mut bars = Vec::new();
+        if vertical {
+            // Leave the corner free when there are two scrollbars
+            let length = viewport.height - if horizontal {width} else {0.0};
+            let track = Rect::new(viewport.right() - width, viewport.y, width, length.max(0.0));
+            let (start, size) = self.thumb(track.height, extent.content.y, viewport.height, self.offset.y, max.y);
+            bars.push(Scrollbar {
+                axis: Axis::Vertical,
+                track,
+                thumb: Rect::new(track.x, track.y + start, width, size),
+                hovered: self.hovered == Some(Axis::Vertical),
+                dragging: matches!(self.drag, Some(Drag::Thumb{axis: Axis::Vertical, ..})),
+            });
+        }
+        if horizontal {
+            let length = viewport.width - if vertical {width} else {0.0};
+            let track = Rect::new(viewport.x, viewport.bottom() - width, length.max(0.0), width);
+            let (start, size) = self.thumb(track.width, extent.content.x, viewport.width, self.offset.x, max.x);
+            bars.push(Scrollbar {
+                axis: Axis::Horizontal,
+                track,
+                thumb: Rect::new(track.x + start, track.y, size, width),
+                hovered: self.hovered == Some(Axis::Horizontal),
+                dragging: matches!(self.drag, Some(Drag::Thumb{axis: Axis::Horizontal, ..})),
+            });
+        }
+
+        return bars;
+    }
+
+    /// Draw the content with `draw_children`, clipped to the viewport, then the scrollbars on top
+    /// of it. Use a pixel camera.
+    pub fn draw(&self, frame: &mut RenderFrame, draw_children: impl FnOnce(&mut RenderFrame)->Result<()>)->Result<()> {
+        let Some(extent) = self.extent else {return draw_children(frame)};
+
+        frame.push_clip(extent.viewport.to_clip());
+        let result = draw_children(frame);
+        frame.pop_clip();
+        result?;
+
+        for bar in self.scrollbars() {
+            let radius = self.scrollbar_width * 0.5;
+            let thumb_color = if bar.hovered || bar.dragging {self.thumb_active_color} else {self.thumb_color};
+            for (rect, color) in [(bar.track, self.track_color), (bar.thumb, thumb_color)] {
+                frame.draw_rounded_rect(&RoundedRect::new(
+                    Point2::new(rect.x, rect.y),
+                    Vec2::new(rect.width, rect.height),
+                    color,
+                ).with_radius(radius));
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Handle an event sent to the container or one of its children. Returns `true` if the view
+    /// used the event.
+    pub fn handle_event(&mut self, ev: &UiEvent)->bool {
+        match &ev.kind {
+            UiEventKind::PointerPressed(MouseButton::Left)=>{
+                let Some(point) = ev.position else {return false};
+                let Some(bar) = self.scrollbars().into_iter().find(|b|b.track.contains(point)) else {return false};
+                self.kinetic.stop();
+
+                if !bar.thumb.contains(point) {
+                    // Clicking the track jumps a page towards the click
+                    let (page, before) = match bar.axis {
+                        Axis::Horizontal=>(Vec2::new(bar.track.width, 0.0), point.x < bar.thumb.x),
+                        Axis::Vertical=>(Vec2::new(0.0, bar.track.height), point.y < bar.thumb.y),
+                    };
+                    self.scroll_by(if before {-page} else {page});
+                    return true;
+                }
+
+                let grab = match bar.axis {
+                    Axis::Horizontal=>point.x - bar.thumb.x,
+                    Axis::Vertical=>point.y - bar.thumb.y,
+                };
+                self.drag = Some(Drag::Thumb{axis: bar.axis, grab});
+                return true;
+            },
+            UiEventKind::PointerMoved=>{
+                let Some(point) = ev.position else {return false};
+                let bars = self.scrollbars();
+                self.hovered = bars.iter()
+                    .find(|b|b.thumb.contains(point))
+                    .map(|b|b.axis);
+
+                let Some(Drag::Thumb{axis, grab}) = self.drag else {return false};
+                let Some(bar) = bars.into_iter().find(|b|b.axis == axis) else {return false};
+                let max = self.max_offset();
+                let offset = match axis {
+                    Axis::Horizontal=>{
+                        let free = bar.track.width - bar.thumb.width;
+                        let x = thumb_offset(point.x - grab - bar.track.x, free, max.x);
+                        Vec2::new(x, self.offset.y)
+                    },
+                    Axis::Vertical=>{
+                        let free = bar.track.height - bar.thumb.height;
+                        let y = thumb_offset(point.y - grab - bar.track.y, free, max.y);
+                        Vec2::new(self.offset.x, y)
+                    },
+                };
+                self.scroll_to(offset);
+                return true;
+            },
+            UiEventKind::PointerReleased(MouseButton::Left)=>{
+                if matches!(self.drag, Some(Drag::Thumb{..})) {
+                    self.drag = None;
+                    return true;
+                }
+            },
+            UiEventKind::PointerLeft=>self.hovered = None,
+            UiEventKind::Wheel(delta, phase)=>{
+                let (delta, touchpad) = match delta {
+                    MouseScrollDelta::LineDelta(x, y)=>(Vec2::new(*x, *y) * self.line_size, false),
+                    MouseScrollDelta::PixelDelta(pos)=>(Vec2::new(pos.x as f32, pos.y as f32), true),
+                };
+                // A plain mouse wheel scrolls horizontally when that is the only way to scroll
+                let delta = if !self.overflow.scrolls_y() && delta.x == 0.0 {
+                    Vec2::new(delta.y, 0.0)
+                } else {
+                    delta
+                };
+
+                if touchpad {
+                    match phase {
+                        TouchPhase::Started=>{
+                            self.kinetic.stop();
+                            self.tracking = true;
+                            self.tracked = Vec2::zero();
+                        },
+                        TouchPhase::Ended=>self.tracking = false,
+                        TouchPhase::Cancelled=>{
+                            self.tracking = false;
+                            self.kinetic.stop();
+                        },
+                        TouchPhase::Moved=>{},
+                    }
+                } else {
+                    self.kinetic.stop();
+                }
+
+                // Wheel deltas move the content, so the offset goes the other way
+                let before = self.offset;
+                let changed = self.scroll_by(-delta);
+                if self.tracking {
+                    self.tracked += self.offset - before;
+                }
+                return changed;
+            },
+            UiEventKind::Touch{id, phase}=>{
+                let Some(point) = ev.position else {return false};
+                match (phase, self.drag) {
+                    (TouchPhase::Started, None)=>{
+                        self.kinetic.stop();
+                        self.drag = Some(Drag::Touch{id: *id, last: point});
+                        self.tracking = true;
+                        self.tracked = Vec2::zero();
+                    },
+                    (TouchPhase::Moved, Some(Drag::Touch{id: touch, last})) if touch == *id=>{
+                        let before = self.offset;
+                        let changed = self.scroll_by(last - point);
+                        self.tracked += self.offset - before;
+                        self.drag = Some(Drag::Touch{id: touch, last: point});
+                        return changed;
+                    },
+                    (TouchPhase::Ended|TouchPhase::Cancelled, Some(Drag::Touch{id: touch, ..})) if touch == *id=>{
+                        self.drag = None;
+                        self.tracking = false;
+                        if *phase == TouchPhase::Cancelled {
+                            self.kinetic.stop();
+                        }
+                    },
+                    _=>{},
+                }
+            },
+            UiEventKind::FocusLost=>if matches!(self.drag, Some(Drag::Thumb{..})) {
+                self.drag = None;
+            },
+            _=>{},
+        }
+
+        return false;
+    }
+
+    /// The start and length of a thumb in a track
+    fn thumb(&self, track: f32, content: f32, viewport: f32, offset: f32, max: f32)->(f32, f32) {
+        let size = (track * viewport / content.max(1.0))
+            .max(self.min_thumb)
+            .min(track);
+        let start = if max > 0.0 {(track - size) * offset / max} else {0.0};
+        return (start, size);
+    }
+}
+
+
+/// Convert a thumb position (from the start of the track) to a scroll offset
+fn thumb_offset(position: f32, free: f32, max: f32)->f32 {
+    if free <= 0.0 {
+        return 0.0;
+    }
+    return (position / free * max).clamp(0.0, max);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinetic_decay() {
+        let mut kinetic = KineticScroll::new(2.0, 1.0);
+        kinetic.velocity = Vec2::new(0.0, 100.0);
+
+        let distance = kinetic.step(0.5);
+        let decay = (-1.0f32).exp();
+        assert!((kinetic.velocity.y - 100.0 * decay).abs() < 1e-3);
+        assert!((distance.y - 100.0 * (1.0 - decay) / 2.0).abs() < 1e-3);
+        assert_eq!(distance.x, 0.0);
+
+        // The distance doesn't depend on how the time is split
+        let mut split = KineticScroll::new(2.0, 1.0);
+        split.velocity = Vec2::new(0.0, 100.0);
+        let total = (0..10).map(|_|split.step(0.05).y).sum::<f32>();
+        assert!((total - distance.y).abs() < 1e-3);
+        assert!((split.velocity.y - kinetic.velocity.y).abs() < 1e-3);
+    }
+
+    #[test]
+    fn kinetic_stops_below_min_velocity() {
+        let mut kinetic = KineticScroll::new(4.0, 10.0);
+        kinetic.velocity = Vec2::new(20.0, 0.0);
+
+        let mut steps = 0;
+        while kinetic.is_moving() {
+            kinetic.step(0.1);
+            steps += 1;
+            assert!(steps < 100, "never stopped");
+        }
+        // 20 * e^(-0.4 * n) drops below 10 after 2 steps, and the step after that stops
+        assert_eq!(steps, 3);
+        assert_eq!(kinetic.step(0.1), Vec2::zero());
+
+        // No time passing also stops it
+        kinetic.velocity = Vec2::new(100.0, 0.0);
+        assert_eq!(kinetic.step(0.0), Vec2::zero());
+        assert!(!kinetic.is_moving());
+    }
+
+    #[test]
+    fn overscroll_clamps() {
+        let mut view = ScrollView::new(Overflow::ScrollY);
+        view.extent = Some(ScrollExtent {
+            viewport: Rect::new(0.0, 0.0, 100.0, 100.0),
+            content: Vec2::new(300.0, 250.0),
+        });
+        view.kinetic.velocity = Vec2::new(50.0, 500.0);
+
+        // X doesn't scroll, so it stays at zero without stopping the motion on Y
+        assert!(view.scroll_to(Vec2::new(20.0, 100.0)));
+        assert_eq!(view.offset, Vec2::new(0.0, 100.0));
+        assert_eq!(view.kinetic.velocity, Vec2::new(0.0, 500.0));
+
+        // Hitting the end stops the kinetic scrolling
+        assert!(view.scroll_by(Vec2::new(0.0, 1000.0)));
+        assert_eq!(view.offset, Vec2::new(0.0, 150.0));
+        assert_eq!(view.kinetic.velocity, Vec2::zero());
+        assert!(!view.scroll_by(Vec2::new(0.0, 10.0)));
+
+        assert!(view.scroll_to(Vec2::new(0.0, -5.0)));
+        assert_eq!(view.offset, Vec2::zero());
+    }
+}
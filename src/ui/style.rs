@@ -0,0 +1,334 @@
+//! Named style classes, like classes in CSS. Classes are defined once in a [`ClassRegistry`]
+//! and applied to containers by name.
+
+
+use std::cell::RefCell;
+use indexmap::IndexMap;
+use fnv::FnvHashSet;
+#[allow(unused)]
+use log::{
+    trace,
+    warn,
+};
+use crate::{
+    render::{
+        RenderFrame,
+        RoundedRect,
+        BoxShadow,
+    },
+    math::*,
+    Color,
+};
+use super::{
+    container::{
+        Container,
+        ContainerStyle,
+    },
+    layout::Layout,
+    Edges,
+    Corners,
+    Shadow,
+    Rect,
+};
+
+
+/// The class applied on top of the focused container's style
+pub const FOCUS_RING_CLASS: &str = "focus-ring";
+
+/// The final style of an element after all of its classes are applied
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ComputedStyle {
+    /// `None` means no border is drawn
+    pub border: Option<Color>,
+    pub border_width: u32,
+    /// `None` means no background is drawn
+    pub bg: Option<Color>,
+    pub padding: Edges,
+    pub margin: Edges,
+    pub corner_radius: Corners,
+    /// `None` means no shadow is drawn
+    pub shadow: Option<Shadow>,
+    pub text_color: Color,
+}
+impl Default for ComputedStyle {
+    fn default()->Self {
+        ComputedStyle {
+            border: None,
+            border_width: 0,
+            bg: None,
+            padding: Edges::ZERO,
+            margin: Edges::ZERO,
+            corner_radius: Corners::ZERO,
+            shadow: None,
+            text_color: Color(0.0, 0.0, 0.0, 1.0),
+        }
+    }
+}
+impl ComputedStyle {
+    /// Fill in the defaults for anything `style` doesn't set
+    pub fn from_style(style: &ContainerStyle)->Self {
+        let default = ComputedStyle::default();
+        return ComputedStyle {
+            border: style.border,
+            border_width: style.border_width.unwrap_or(default.border_width),
+            bg: style.bg,
+            padding: style.padding.unwrap_or(default.padding),
+            margin: style.margin.unwrap_or(default.margin),
+            corner_radius: style.corner_radius.unwrap_or(default.corner_radius),
+            shadow: style.shadow,
+            text_color: style.text_color.unwrap_or(default.text_color),
+        };
+    }
+
+    /// Overwrite every property that is set in `style`
+    pub fn apply(&mut self, style: &ContainerStyle) {
+        self.border = style.border.or(self.border);
+        self.border_width = style.border_width.unwrap_or(self.border_width);
+        self.bg = style.bg.or(self.bg);
+        self.padding = style.padding.unwrap_or(self.padding);
+        self.margin = style.margin.unwrap_or(self.margin);
+        self.corner_radius = style.corner_radius.unwrap_or(self.corner_radius);
+        self.shadow = style.shadow.or(self.shadow);
+        self.text_color = style.text_color.unwrap_or(self.text_color);
+    }
+
+    /// The background, border and shadow of an element covering `rect`. Returns `None` if there
+    /// is nothing to draw.
+    pub fn background(&self, rect: Rect)->Option<RoundedRect> {
+        let border = self.border.filter(|_|self.border_width > 0);
+        if self.bg.is_none() && border.is_none() && self.shadow.is_none() {
+            return None;
+        }
+
+        let mut background = RoundedRect::new(
+            Point2::new(rect.x, rect.y),
+            Vec2::new(rect.width, rect.height),
+            self.bg.unwrap_or(Color(0.0, 0.0, 0.0, 0.0)),
+        ).with_radii(self.corner_radius.to_array());
+        if let Some(border) = border {
+            background = background.with_border(self.border_width as f32, border);
+        }
+        if let Some(shadow) = self.shadow {
+            background = background.with_shadow(BoxShadow {
+                color: shadow.color,
+                offset: Vec2::new(shadow.offset_x, shadow.offset_y),
+                blur: shadow.blur,
+                spread: shadow.spread,
+            });
+        }
+
+        return Some(background);
+    }
+}
+
+/// Draw the background of every node in `layout` from bottom to top. Each one is clipped to its
+/// clipping ancestors. Use a pixel camera.
+pub fn draw_backgrounds(layout: &Layout, frame: &mut RenderFrame) {
+    for index in layout.draw_order().iter().copied() {
+        let node = &layout.nodes[index];
+        let Some(background) = node.style.background(node.rect) else {continue};
+
+        if let Some(clip) = node.clip {
+            frame.push_clip(clip.to_clip());
+        }
+        frame.draw_rounded_rect(&background);
+        if node.clip.is_some() {
+            frame.pop_clip();
+        }
+    }
+}
+
+/// All of the known style classes
+#[derive(Debug, Clone, Default)]
+pub struct ClassRegistry {
+    pub classes: IndexMap<String, ContainerStyle>,
+    /// Unknown class names that were already logged, so each one is only warned about once
+    warned: RefCell<FnvHashSet<String>>,
+}
+impl ClassRegistry {
+    pub fn new()->Self {
+        Self::default()
+    }
+
+    /// Define a class, replacing any class with the same name
+    pub fn define(&mut self, name: impl Into<String>, style: ContainerStyle)->&mut Self {
+        self.classes.insert(name.into(), style);
+        return self;
+    }
+
+    /// Remove a class and return its style. The order of the other classes is kept.
+    pub fn remove(&mut self, name: &str)->Option<ContainerStyle> {
+        self.classes.shift_remove(name)
+    }
+
+    #[inline]
+    pub fn get(&self, name: &str)->Option<&ContainerStyle> {
+        self.classes.get(name)
+    }
+
+    /// Combine the named classes in order, then apply `inline` on top. Unknown classes are
+    /// skipped, with a warning the first time each name is seen.
+    pub fn combine<S: AsRef<str>>(&self, classes: &[S], inline: &ContainerStyle)->ContainerStyle {
+        let mut style = ContainerStyle::default();
+        for name in classes {
+            let name = name.as_ref();
+            match self.classes.get(name) {
+                Some(class)=>style.apply(class),
+                None=>if !self.warned.borrow().contains(name) {
+                    warn!("Unknown style class `{name}`");
+                    self.warned.borrow_mut().insert(name.to_string());
+                },
+            }
+        }
+        style.apply(inline);
+
+        return style;
+    }
+
+    /// Resolve the style of a container. The container's own padding is used unless a class or
+    /// the inline style sets one.
+    pub fn resolve(&self, container: &Container)->ComputedStyle {
+        let mut style = ContainerStyle {
+            padding: Some(container.padding),
+            ..ContainerStyle::default()
+        };
+        style.apply(&self.combine(&container.classes, &container.style));
+
+        return ComputedStyle::from_style(&style);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::ui::Size;
+
+    /// Every warning logged by the tests
+    static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct TestLogger;
+    impl log::Log for TestLogger {
+        fn enabled(&self, metadata: &log::Metadata)->bool {
+            metadata.level() <= log::Level::Warn
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                WARNINGS.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    const RED: Color = Color {r: 1.0, g: 0.0, b: 0.0, a: 1.0};
+    const BLUE: Color = Color {r: 0.0, g: 0.0, b: 1.0, a: 1.0};
+    const GREEN: Color = Color {r: 0.0, g: 1.0, b: 0.0, a: 1.0};
+
+    fn registry()->ClassRegistry {
+        let mut registry = ClassRegistry::new();
+        registry
+            .define("red", ContainerStyle {
+                bg: Some(RED),
+                border_width: Some(1),
+                ..ContainerStyle::default()
+            })
+            .define("blue", ContainerStyle {
+                bg: Some(BLUE),
+                padding: Some(Edges::all(4)),
+                ..ContainerStyle::default()
+            });
+        return registry;
+    }
+
+    #[test]
+    fn later_classes_override() {
+        let registry = registry();
+        let style = registry.combine(&["red", "blue"], &ContainerStyle::default());
+        assert_eq!(style.bg, Some(BLUE));
+        // Properties the later class doesn't set are kept
+        assert_eq!(style.border_width, Some(1));
+        assert_eq!(style.padding, Some(Edges::all(4)));
+
+        let style = registry.combine(&["blue", "red"], &ContainerStyle::default());
+        assert_eq!(style.bg, Some(RED));
+    }
+
+    #[test]
+    fn inline_overrides_classes() {
+        let registry = registry();
+        let inline = ContainerStyle {
+            bg: Some(GREEN),
+            ..ContainerStyle::default()
+        };
+        let style = registry.combine(&["red", "blue"], &inline);
+        assert_eq!(style.bg, Some(GREEN));
+        assert_eq!(style.border_width, Some(1));
+    }
+
+    #[test]
+    fn resolve_padding() {
+        let registry = registry();
+        let container = Container::new(Size::Fill, Size::Fill)
+            .with_padding(Edges::all(9))
+            .with_class("red");
+        assert_eq!(registry.resolve(&container).padding, Edges::all(9));
+        assert_eq!(registry.resolve(&container).border_width, 1);
+
+        // A class's padding replaces the container's, and the inline style replaces both
+        let container = container.with_class("blue");
+        assert_eq!(registry.resolve(&container).padding, Edges::all(4));
+        let container = container.with_style(ContainerStyle {
+            padding: Some(Edges::all(2)),
+            ..ContainerStyle::default()
+        });
+        assert_eq!(registry.resolve(&container).padding, Edges::all(2));
+
+        // Nothing set falls back to the defaults
+        let plain = Container::new(Size::Fill, Size::Fill);
+        assert_eq!(registry.resolve(&plain), ComputedStyle::default());
+    }
+
+    #[test]
+    fn redefine_and_remove() {
+        let mut registry = registry();
+        registry.define("green", ContainerStyle::default());
+        registry.define("red", ContainerStyle {
+            bg: Some(GREEN),
+            ..ContainerStyle::default()
+        });
+        // Redefined classes keep their place
+        assert_eq!(registry.classes.keys().collect::<Vec<_>>(), ["red", "blue", "green"]);
+        assert_eq!(registry.combine(&["red"], &ContainerStyle::default()).bg, Some(GREEN));
+
+        assert!(registry.remove("blue").is_some());
+        assert!(registry.remove("blue").is_none());
+        assert_eq!(registry.classes.keys().collect::<Vec<_>>(), ["red", "green"]);
+    }
+
+    #[test]
+    fn unknown_classes_warn_once() {
+        log::set_logger(&TestLogger).expect("Only this test sets a logger");
+        log::set_max_level(log::LevelFilter::Warn);
+        let unknown = format!("missing-{}", crate::new_uuid());
+        let other = format!("missing-{}", crate::new_uuid());
+        let count = |name: &str|WARNINGS.lock().unwrap()
+            .iter()
+            .filter(|w|w.contains(name))
+            .count();
+
+        let registry = registry();
+        for _ in 0..3 {
+            let style = registry.combine(&[unknown.as_str(), "red", other.as_str()], &ContainerStyle::default());
+            assert_eq!(style.bg, Some(RED));
+        }
+        assert_eq!(count(&unknown), 1);
+        assert_eq!(count(&other), 1);
+
+        // A clone remembers what was already warned about
+        registry.clone().combine(&[unknown.as_str()], &ContainerStyle::default());
+        assert_eq!(count(&unknown), 1);
+    }
+}
@@ -0,0 +1,283 @@
+//! Style classes loaded from a [RON](https://github.com/ron-rs/ron) file, so they can be changed
+//! without recompiling. A stylesheet is a map from class name to [`ContainerStyle`]:
+//!
+//! ```ron
+//! {
+//!     "panel": (
+//!         bg: (r: 0.1, g: 0.1, b: 0.1, a: 1.0),
+//!         padding: (top: 4, right: 8, bottom: 4, left: 8),
+//!     ),
+//!     "rounded": (corner_radius: 6.0),
+//!     "tab": (corner_radius: (top_left: 6.0, top_right: 6.0)),
+//!     "raised": (shadow: (offset_y: 2.0, blur: 8.0)),
+//! }
+//! ```
+//!
+//! Optional properties don't need to be wrapped in `Some(...)`, and any property that isn't
+//! listed is left unset.
+
+
+use indexmap::IndexMap;
+use ron::{
+    extensions::Extensions,
+    Options,
+};
+use thiserror::Error;
+#[allow(unused)]
+use log::{
+    trace,
+    debug,
+    warn,
+};
+use std::{
+    path::{
+        PathBuf,
+        Path,
+    },
+    time::SystemTime,
+};
+use super::{
+    container::ContainerStyle,
+    style::ClassRegistry,
+};
+
+
+#[derive(Debug, Error)]
+pub enum StyleSheetError {
+    #[error("Could not read stylesheet `{}`: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{}:{line}:{column}: {message}", path.display())]
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+/// A stylesheet file that can be reloaded when it changes on disk
+#[derive(Debug)]
+pub struct StyleSheet {
+    pub path: PathBuf,
+    /// The modification time of the file when it was last loaded
+    pub modified: Option<SystemTime>,
+    pub classes: IndexMap<String, ContainerStyle>,
+    /// The class names put in a registry the last time this was applied
+    defined: Vec<String>,
+}
+impl StyleSheet {
+    pub fn load(path: impl AsRef<Path>)->Result<Self, StyleSheetError> {
+        let mut sheet = StyleSheet {
+            path: path.as_ref().to_path_buf(),
+            modified: None,
+            classes: IndexMap::new(),
+            defined: Vec::new(),
+        };
+        sheet.reload()?;
+
+        return Ok(sheet);
+    }
+
+    /// Parse stylesheet text. `path` is only used for error messages.
+    pub fn parse(path: &Path, text: &str)->Result<IndexMap<String, ContainerStyle>, StyleSheetError> {
+        Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_str(text)
+            .map_err(|e|StyleSheetError::Parse {
+                path: path.to_path_buf(),
+                line: e.position.line,
+                column: e.position.col,
+                message: e.code.to_string(),
+            })
+    }
+
+    /// Read and parse the file again. If this fails, then the old classes and modification time
+    /// are kept, so [`StyleSheet::changed`] stays `true` and the file is tried again.
+    pub fn reload(&mut self)->Result<(), StyleSheetError> {
+        let io_err = |source|StyleSheetError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|meta|meta.modified())
+            .ok();
+        let text = std::fs::read_to_string(&self.path)
+            .map_err(io_err)?;
+
+        self.classes = Self::parse(&self.path, &text)?;
+        self.modified = modified;
+        debug!("Loaded {} classes from `{}`", self.classes.len(), self.path.display());
+
+        return Ok(());
+    }
+
+    /// Check if the file has changed since it was last loaded. Meant to be called once per frame.
+    pub fn changed(&self)->bool {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|meta|meta.modified())
+            .ok();
+        return modified.is_some() && modified != self.modified;
+    }
+
+    /// Reload the file if it changed and put the new classes in `registry`. Returns `true` if the
+    /// registry was updated. A file that fails to parse returns the error on every call until it
+    /// is fixed.
+    pub fn reload_if_changed(&mut self, registry: &mut ClassRegistry)->Result<bool, StyleSheetError> {
+        if !self.changed() {
+            return Ok(false);
+        }
+
+        self.reload()?;
+        self.apply(registry);
+
+        return Ok(true);
+    }
+
+    /// Define every class in this stylesheet in `registry`. Classes that were defined the last
+    /// time this was applied but have since been removed from the file are removed from
+    /// `registry`.
+    pub fn apply(&mut self, registry: &mut ClassRegistry) {
+        for name in self.defined.iter().filter(|name|!self.classes.contains_key(*name)) {
+            registry.remove(name);
+        }
+        for (name, style) in self.classes.iter() {
+            registry.define(name.clone(), *style);
+        }
+        self.defined = self.classes.keys().cloned().collect();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::{
+        ui::{
+            Corners,
+            Edges,
+            Shadow,
+        },
+        Color,
+    };
+
+    fn parse(text: &str)->Result<IndexMap<String, ContainerStyle>, StyleSheetError> {
+        StyleSheet::parse(Path::new("test.ron"), text)
+    }
+
+    /// Write `text` to `path` with a modification time `seconds` after the epoch, so changes are
+    /// seen no matter how coarse the file system's times are
+    fn write(path: &Path, text: &str, seconds: u64) {
+        std::fs::write(path, text).unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)).unwrap();
+    }
+
+    #[test]
+    fn parse_properties() {
+        let classes = parse(r#"{
+            "panel": (
+                bg: (r: 0.1, g: 0.2, b: 0.3, a: 1.0),
+                padding: (top: 4, right: 8),
+            ),
+            "rounded": (corner_radius: 6.0),
+            "tab": (corner_radius: (top_left: 6.0, top_right: 5.0)),
+            "raised": (shadow: (offset_y: 4.0)),
+        }"#).unwrap();
+
+        assert_eq!(classes.keys().collect::<Vec<_>>(), ["panel", "rounded", "tab", "raised"]);
+        let panel = classes["panel"];
+        assert_eq!(panel.bg, Some(Color(0.1, 0.2, 0.3, 1.0)));
+        assert_eq!(panel.padding, Some(Edges {top: 4, right: 8, bottom: 0, left: 0}));
+        assert_eq!(panel.border, None);
+        assert_eq!(panel.corner_radius, None);
+
+        // A single radius or a radius for each corner
+        assert_eq!(classes["rounded"].corner_radius, Some(Corners::all(6.0)));
+        assert_eq!(classes["tab"].corner_radius, Some(Corners {
+            top_left: 6.0,
+            top_right: 5.0,
+            bottom_right: 0.0,
+            bottom_left: 0.0,
+        }));
+        assert_eq!(classes["raised"].shadow, Some(Shadow {
+            offset_y: 4.0,
+            ..Shadow::default()
+        }));
+    }
+
+    #[test]
+    fn round_trip() {
+        let style = ContainerStyle {
+            border: Some(Color(1.0, 0.0, 0.0, 1.0)),
+            border_width: Some(2),
+            bg: Some(Color(0.0, 0.0, 1.0, 0.5)),
+            padding: Some(Edges::symmetric(4, 8)),
+            margin: Some(Edges::all(1)),
+            corner_radius: Some(Corners {
+                top_left: 1.0,
+                top_right: 2.0,
+                bottom_right: 3.0,
+                bottom_left: 4.0,
+            }),
+            shadow: Some(Shadow::default()),
+            text_color: Some(Color(0.5, 0.5, 0.5, 1.0)),
+        };
+        let mut classes = IndexMap::new();
+        classes.insert("full".to_string(), style);
+        classes.insert("empty".to_string(), ContainerStyle::default());
+
+        // Written with and without `Some(...)`
+        let explicit = ron::to_string(&classes).unwrap();
+        assert_eq!(parse(&explicit).unwrap(), classes);
+        let implicit = Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .to_string(&classes)
+            .unwrap();
+        assert!(!implicit.contains("Some"), "{implicit}");
+        assert_eq!(parse(&implicit).unwrap(), classes);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = parse("{\n    \"a\": (bg: 1.0),\n    \"b\": (\n        nope: 1,\n    ),\n}").unwrap_err();
+        let StyleSheetError::Parse{line, column, ..} = err else {panic!("{err}")};
+        assert_eq!(line, 2);
+        assert!(column > 1);
+
+        let err = parse("{\n    \"b\": (\n        nope: 1,\n    ),\n}").unwrap_err();
+        let StyleSheetError::Parse{line, ref message, ..} = err else {panic!("{err}")};
+        assert_eq!(line, 3);
+        assert!(message.contains("nope"), "{message}");
+        assert!(err.to_string().starts_with("test.ron:3:"), "{err}");
+    }
+
+    #[test]
+    fn reload_retries_after_errors() {
+        let path = std::env::temp_dir().join(format!("stylesheet-{}.ron", crate::new_uuid()));
+        write(&path, r#"{"a": (border_width: 1)}"#, 1000);
+        let mut sheet = StyleSheet::load(&path).unwrap();
+        let mut registry = ClassRegistry::default();
+        sheet.apply(&mut registry);
+        assert!(!sheet.changed());
+
+        // A broken save keeps the old classes and is tried again
+        write(&path, r#"{"a": (border_width: "#, 2000);
+        assert!(sheet.changed());
+        assert!(sheet.reload_if_changed(&mut registry).is_err());
+        assert_eq!(sheet.classes["a"].border_width, Some(1));
+        assert!(sheet.changed());
+        assert!(sheet.reload_if_changed(&mut registry).is_err());
+
+        write(&path, r#"{"a": (border_width: 2)}"#, 3000);
+        assert!(sheet.reload_if_changed(&mut registry).unwrap());
+        assert_eq!(registry.get("a").unwrap().border_width, Some(2));
+        assert!(!sheet.changed());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(sheet.reload(), Err(StyleSheetError::Io{..})));
+    }
+}
@@ -0,0 +1,556 @@
+//! Text display. Fonts are loaded into [`Fonts`], strings are laid out on the CPU with
+//! [`TextLayout`], and glyphs are rasterized into a [`GlyphCache`] that is drawn with
+//! [`Shape2D::TexturePolygon`] quads.
+
+
+use fontdue::{
+    Font,
+    FontSettings,
+};
+use anyhow::{
+    Result,
+    anyhow,
+    bail,
+};
+#[allow(unused)]
+use log::{
+    trace,
+    warn,
+};
+use std::{
+    ops::Range,
+    path::Path,
+};
+use crate::{
+    render::{
+        Renderer,
+        RenderFrame,
+        Shape2D,
+        ShapeID,
+    },
+    math::*,
+    Uuid,
+    IdMap,
+    Color,
+};
+use super::{
+    glyph_cache::{
+        GlyphCache,
+        GlyphKey,
+    },
+    Rect,
+};
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct FontID(pub Uuid);
+
+/// All of the loaded fonts
+#[derive(Default)]
+pub struct Fonts {
+    pub fonts: IdMap<Font>,
+}
+impl Fonts {
+    pub fn new()->Self {
+        Self::default()
+    }
+
+    /// Load a TTF or OTF font file
+    pub fn load(&mut self, path: impl AsRef<Path>)->Result<FontID> {
+        let bytes = std::fs::read(path)?;
+        return self.add_bytes(&bytes);
+    }
+
+    pub fn add_bytes(&mut self, bytes: &[u8])->Result<FontID> {
+        let font = Font::from_bytes(bytes, FontSettings::default())
+            .map_err(|e|anyhow!("Could not load font: {e}"))?;
+        let id = crate::new_uuid();
+        self.fonts.insert(id, font);
+
+        return Ok(FontID(id));
+    }
+
+    #[inline]
+    pub fn get(&self, id: FontID)->Option<&Font> {
+        self.fonts.get(&id.0)
+    }
+
+    pub fn drop_font(&mut self, id: FontID) {
+        self.fonts.remove(&id.0);
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+impl TextAlign {
+    fn factor(&self)->f32 {
+        match self {
+            Self::Left=>0.0,
+            Self::Center=>0.5,
+            Self::Right=>1.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextStyle {
+    pub font: FontID,
+    /// Font size in pixels per em
+    pub size: f32,
+    /// Multiplier for the font's line height
+    pub line_height: f32,
+    pub align: TextAlign,
+    pub color: Color,
+}
+impl TextStyle {
+    pub fn new(font: FontID, size: f32)->Self {
+        TextStyle {
+            font,
+            size,
+            line_height: 1.0,
+            align: TextAlign::Left,
+            color: Color(0.0, 0.0, 0.0, 1.0),
+        }
+    }
+}
+
+/// A glyph placed on a line. Positions are in pixels relative to the top left of the text.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LaidGlyph {
+    pub glyph_index: u16,
+    /// Byte offset of the character in the source string
+    pub byte_offset: usize,
+    pub ch: char,
+    /// Pen position. This is not rounded, so it can be between pixels.
+    pub x: f32,
+    pub advance: f32,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLine {
+    /// Byte range of the line in the source string, not including the line break
+    pub bytes: Range<usize>,
+    /// Range of the glyphs in [`TextLayout::glyphs`]
+    pub glyphs: Range<usize>,
+    /// Top of the line
+    pub y: f32,
+    pub baseline: f32,
+    /// Width without trailing whitespace
+    pub width: f32,
+}
+
+/// The result of laying out a string
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextLayout {
+    pub glyphs: Vec<LaidGlyph>,
+    pub lines: Vec<TextLine>,
+    pub width: f32,
+    pub height: f32,
+    pub line_height: f32,
+}
+impl TextLayout {
+    /// Lay out `text`, wrapping lines at word boundaries so they fit in `max_width`. Words that
+    /// are wider than `max_width` are broken between characters.
+    pub fn new(font: &Font, text: &str, style: &TextStyle, max_width: Option<f32>)->Self {
+        let px = style.size;
+        let (ascent, line_size) = match font.horizontal_line_metrics(px) {
+            Some(metrics)=>(metrics.ascent, metrics.new_line_size),
+            None=>(px * 0.8, px),
+        };
+        let line_height = line_size * style.line_height;
+        let baseline_offset = ascent + (line_height - line_size) / 2.0;
+
+        let mut layout = TextLayout {
+            line_height,
+            ..TextLayout::default()
+        };
+
+        let mut paragraph_start = 0;
+        for paragraph in text.split('\n') {
+            let mut glyphs = paragraph.char_indices()
+                .map(|(offset, ch)|{
+                    let glyph_index = font.lookup_glyph_index(ch);
+                    LaidGlyph {
+                        glyph_index,
+                        byte_offset: paragraph_start + offset,
+                        ch,
+                        x: 0.0,
+                        advance: font.metrics_indexed(glyph_index, px).advance_width,
+                        line: 0,
+                    }
+                })
+                .collect::<Vec<_>>();
+            for i in 1..glyphs.len() {
+                let kern = font.horizontal_kern_indexed(glyphs[i - 1].glyph_index, glyphs[i].glyph_index, px);
+                glyphs[i - 1].advance += kern.unwrap_or(0.0);
+            }
+
+            let mut start = 0;
+            let mut x = 0.0;
+            let mut break_at = None;
+            let mut i = 0;
+            while i < glyphs.len() {
+                let glyph = glyphs[i];
+                let overflows = max_width.is_some_and(|max|x + glyph.advance > max);
+                if overflows && !glyph.ch.is_whitespace() && i > start {
+                    let end = break_at.filter(|b|*b > start).unwrap_or(i);
+                    layout.push_line(&glyphs[start..end], paragraph_start, baseline_offset);
+                    start = end;
+                    x = glyphs[start..i].iter().map(|g|g.advance).sum();
+                    break_at = None;
+                    continue;
+                }
+
+                x += glyph.advance;
+                if glyph.ch.is_whitespace() {
+                    break_at = Some(i + 1);
+                }
+                i += 1;
+            }
+            let end_offset = paragraph_start + paragraph.len();
+            layout.push_line(&glyphs[start..], end_offset, baseline_offset);
+
+            paragraph_start = end_offset + 1;
+        }
+
+        layout.width = layout.lines.iter()
+            .map(|line|line.width)
+            .fold(0.0, f32::max);
+        layout.height = layout.lines.len() as f32 * line_height;
+
+        let align_width = max_width.unwrap_or(layout.width);
+        let factor = style.align.factor();
+        for line in layout.lines.iter() {
+            let offset = ((align_width - line.width) * factor).max(0.0);
+            for glyph in layout.glyphs[line.glyphs.clone()].iter_mut() {
+                glyph.x += offset;
+            }
+        }
+
+        return layout;
+    }
+
+    /// Add a line with the given glyphs. `empty_offset` is the byte offset used if there are no
+    /// glyphs.
+    fn push_line(&mut self, glyphs: &[LaidGlyph], empty_offset: usize, baseline_offset: f32) {
+        let line = self.lines.len();
+        let y = line as f32 * self.line_height;
+        let first = self.glyphs.len();
+
+        let mut x = 0.0;
+        let mut width = 0.0;
+        for glyph in glyphs {
+            self.glyphs.push(LaidGlyph {
+                x,
+                line,
+                ..*glyph
+            });
+            x += glyph.advance;
+            if !glyph.ch.is_whitespace() {
+                width = x;
+            }
+        }
+
+        let bytes = match (glyphs.first(), glyphs.last()) {
+            (Some(first), Some(last))=>first.byte_offset..last.byte_offset + last.ch.len_utf8(),
+            _=>empty_offset..empty_offset,
+        };
+        self.lines.push(TextLine {
+            bytes,
+            glyphs: first..self.glyphs.len(),
+            y,
+            baseline: y + baseline_offset,
+            width,
+        });
+    }
+}
+
+/// The key used to tell if a [`Text`] needs its shape rebuilt
+#[derive(Debug, Clone, PartialEq)]
+struct TextCache {
+    text: String,
+    style: TextStyle,
+    rect: Rect,
+    wrap: bool,
+    cache_generation: u64,
+}
+
+/// A text display element. Call [`Text::prepare`] after layout and [`Text::draw`] while
+/// rendering.
+pub struct Text {
+    pub text: String,
+    pub style: TextStyle,
+    /// Wrap lines at the width of the text's rectangle
+    pub wrap: bool,
+    /// The layout from the last call to [`Text::prepare`]
+    pub layout: TextLayout,
+    /// One shape for each glyph cache page used by the text
+    shapes: Vec<ShapeID>,
+    /// The glyphs in `shapes`, so they can be kept in the cache while the shapes are reused
+    glyph_keys: Vec<GlyphKey>,
+    cache: Option<TextCache>,
+}
+impl Text {
+    pub fn new(text: impl Into<String>, style: TextStyle)->Self {
+        Text {
+            text: text.into(),
+            style,
+            wrap: true,
+            layout: TextLayout::default(),
+            shapes: Vec::new(),
+            glyph_keys: Vec::new(),
+            cache: None,
+        }
+    }
+
+    /// Lay out the text inside of `rect` and rebuild the glyph quads if anything changed. If
+    /// [`Text::wrap`] is set, then lines are wrapped at the width of `rect`.
+    pub fn prepare(&mut self, renderer: &mut Renderer, fonts: &Fonts, glyphs: &mut GlyphCache, rect: Rect)->Result<()> {
+        let cache = TextCache {
+            text: self.text.clone(),
+            // The color is applied when drawing, so it doesn't need new shapes
+            style: TextStyle {
+                color: Color::WHITE,
+                ..self.style
+            },
+            rect,
+            wrap: self.wrap,
+            cache_generation: glyphs.generation,
+        };
+        if self.cache.as_ref() == Some(&cache) {
+            for key in self.glyph_keys.iter() {
+                glyphs.touch(key);
+            }
+            return Ok(());
+        }
+
+        let Some(font) = fonts.get(self.style.font) else {bail!("Font does not exist")};
+        self.layout = TextLayout::new(font, &self.text, &self.style, self.wrap.then_some(rect.width));
+
+        let mut placed = Vec::new();
+        self.glyph_keys.clear();
+        for glyph in self.layout.glyphs.iter() {
+            let line = &self.layout.lines[glyph.line];
+            let (x, subpixel) = GlyphCache::snap(rect.x + glyph.x);
+            let Some(cached) = glyphs.glyph(font, self.style.font, self.style.size, glyph.glyph_index, subpixel) else {break};
+            if cached.width == 0 || cached.height == 0 {
+                continue;
+            }
+            self.glyph_keys.push(GlyphKey::new(self.style.font, self.style.size, glyph.glyph_index, subpixel));
+            let top_left = Vec2::new(
+                x + cached.xmin as f32,
+                (rect.y + line.baseline).round() - cached.ymin as f32 - cached.height as f32,
+            );
+            placed.push((top_left, cached));
+        }
+
+        glyphs.upload(renderer)?;
+        for old in self.shapes.drain(..) {
+            renderer.drop_shape2d(old);
+        }
+
+        let page_size = Vec2::broadcast(glyphs.page_size as f32);
+        for (page_index, page) in glyphs.pages.iter().enumerate() {
+            let Some(texture) = page.image_id else {continue};
+
+            let mut vertices = Vec::new();
+            let mut uvs = Vec::new();
+            let mut indices = Vec::new();
+            for (top_left, cached) in placed.iter().filter(|(_, c)|c.page == page_index) {
+                if vertices.len() + 4 > u16::MAX as usize {
+                    warn!("Text has too many glyphs on one page, some will not be drawn");
+                    break;
+                }
+                let size = Vec2::new(cached.width as f32, cached.height as f32);
+                let uv = Vec2::new(cached.x as f32, cached.y as f32);
+
+                let first = vertices.len() as u16;
+                for corner in [Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0), Vec2::new(1.0, 1.0)] {
+                    vertices.push(*top_left + corner * size);
+                    uvs.push((uv + corner * size) / page_size);
+                }
+                indices.extend([first, first + 1, first + 2, first + 3, first + 2, first + 1]);
+            }
+
+            if !indices.is_empty() {
+                self.shapes.push(renderer.add_shape2d(Shape2D::TexturePolygon {
+                    texture,
+                    uvs,
+                    vertices,
+                    indices,
+                })?);
+            }
+        }
+
+        self.cache = Some(TextCache {
+            cache_generation: glyphs.generation,
+            ..cache
+        });
+
+        return Ok(());
+    }
+
+    /// Draw the text in [`TextStyle::color`]. The glyphs are placed in window pixels, so draw with
+    /// a pixel camera (see [`Camera2D::pixels`](crate::render::Camera2D::pixels)).
+    pub fn draw(&self, frame: &mut RenderFrame)->Result<()> {
+        for shape in self.shapes.iter() {
+            frame.shape2d_tinted(*shape, Transform2::identity(), self.style.color, 1.0)?;
+        }
+
+        return Ok(());
+    }
+
+    /// Free the GPU resources used by this text
+    pub fn drop_shapes(&mut self, renderer: &mut Renderer) {
+        for shape in self.shapes.drain(..) {
+            renderer.drop_shape2d(shape);
+        }
+        self.glyph_keys.clear();
+        self.cache = None;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cantarell, under the SIL Open Font License 1.1
+    const FONT: &[u8] = include_bytes!("../../assets/fonts/Cantarell-Regular.ttf");
+
+    fn font()->Font {
+        Font::from_bytes(FONT, FontSettings::default()).unwrap()
+    }
+
+    fn style()->TextStyle {
+        TextStyle::new(FontID(Uuid::nil()), 20.0)
+    }
+
+    /// The width of `text` on a single line
+    fn width(font: &Font, text: &str)->f32 {
+        TextLayout::new(font, text, &style(), None).width
+    }
+
+    /// The text of each line without trailing whitespace
+    fn lines<'a>(layout: &TextLayout, text: &'a str)->Vec<&'a str> {
+        layout.lines.iter()
+            .map(|line|text[line.bytes.clone()].trim_end())
+            .collect()
+    }
+
+    #[test]
+    fn no_max_width() {
+        let font = font();
+        let text = "hello world, this is one long line";
+        let layout = TextLayout::new(&font, text, &style(), None);
+
+        assert_eq!(lines(&layout, text), [text]);
+        assert_eq!(layout.lines[0].bytes, 0..text.len());
+        assert_eq!(layout.glyphs.len(), text.chars().count());
+        assert!(layout.width > 0.0);
+        assert_eq!(layout.height, layout.line_height);
+        // Glyphs are placed one after another
+        for pair in layout.glyphs.windows(2) {
+            assert!((pair[0].x + pair[0].advance - pair[1].x).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn word_wrap() {
+        let font = font();
+        let text = "hello world foo bar";
+        let max_width = width(&font, "hello world") + 1.0;
+        let layout = TextLayout::new(&font, text, &style(), Some(max_width));
+
+        assert_eq!(lines(&layout, text), ["hello world", "foo bar"]);
+        // The space at the break stays on the first line, but doesn't count towards its width
+        assert_eq!(layout.lines[0].bytes, 0..12);
+        assert_eq!(layout.lines[1].bytes, 12..text.len());
+        assert!((layout.lines[0].width - width(&font, "hello world")).abs() < 1e-3);
+        for (i, line) in layout.lines.iter().enumerate() {
+            assert!(line.width <= max_width);
+            assert_eq!(line.y, i as f32 * layout.line_height);
+            assert!(layout.glyphs[line.glyphs.clone()].iter().all(|g|g.line == i));
+        }
+        // The second line starts at the left again
+        assert_eq!(layout.glyphs[layout.lines[1].glyphs.start].x, 0.0);
+    }
+
+    #[test]
+    fn newlines() {
+        let font = font();
+        let text = "one\n\ntwo three\n";
+        let layout = TextLayout::new(&font, text, &style(), Some(1000.0));
+
+        assert_eq!(lines(&layout, text), ["one", "", "two three", ""]);
+        assert_eq!(layout.lines[1].bytes, 4..4);
+        assert_eq!(layout.lines[3].bytes, text.len()..text.len());
+        assert_eq!(layout.lines[1].width, 0.0);
+        assert_eq!(layout.height, 4.0 * layout.line_height);
+        // Line breaks don't get glyphs
+        assert!(layout.glyphs.iter().all(|g|g.ch != '\n'));
+        assert_eq!(layout.glyphs[3].byte_offset, 5);
+    }
+
+    #[test]
+    fn long_words() {
+        let font = font();
+        let text = "a verylongwordthatdoesnotfit b";
+        let max_width = width(&font, "verylong") + 1.0;
+        let layout = TextLayout::new(&font, text, &style(), Some(max_width));
+
+        // The long word is broken between characters, and no text is lost
+        assert!(layout.lines.len() > 3, "{:?}", lines(&layout, text));
+        assert_eq!(lines(&layout, text)[0], "a");
+        let joined = layout.lines.iter()
+            .map(|line|&text[line.bytes.clone()])
+            .collect::<String>();
+        assert_eq!(joined, text);
+        for line in layout.lines.iter() {
+            assert!(line.width <= max_width, "{line:?}");
+        }
+
+        // Each line has at least one character, even if it doesn't fit
+        let layout = TextLayout::new(&font, "abc", &style(), Some(1.0));
+        assert_eq!(lines(&layout, "abc"), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn multibyte_offsets() {
+        let font = font();
+        let text = "héllo\nwörld";
+        let layout = TextLayout::new(&font, text, &style(), None);
+
+        assert_eq!(lines(&layout, text), ["héllo", "wörld"]);
+        assert_eq!(layout.lines[0].bytes, 0..6);
+        assert_eq!(layout.lines[1].bytes, 7..13);
+        let offsets = layout.glyphs.iter().map(|g|g.byte_offset).collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 1, 3, 4, 5, 7, 8, 10, 11, 12]);
+    }
+
+    #[test]
+    fn alignment() {
+        let font = font();
+        let line_width = width(&font, "hi");
+        let centered = TextStyle {
+            align: TextAlign::Center,
+            ..style()
+        };
+        let layout = TextLayout::new(&font, "hi", &centered, Some(100.0));
+        assert!((layout.glyphs[0].x - (100.0 - line_width) / 2.0).abs() < 1e-3);
+
+        let right = TextStyle {
+            align: TextAlign::Right,
+            ..style()
+        };
+        let layout = TextLayout::new(&font, "hi", &right, Some(100.0));
+        assert!((layout.glyphs[0].x - (100.0 - line_width)).abs() < 1e-3);
+    }
+}